@@ -0,0 +1,240 @@
+// mod bufferpool;
+// src/bufferpool.rs
+// The buffer pool keeps the most recently used pages of a file in memory.
+// Pages are read from disk on a miss and the least recently used page is evicted when the pool is full.
+// Dirty pages are written back to the file when they are evicted or flushed.
+// A page stays dirty, and in memory, until its write succeeds, so a failed write can be retried.
+// The storage manager does not read through the pool yet, so the module is allowed to be unused until it does.
+#![allow(dead_code)]
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::io::{Error, ErrorKind, Result};
+
+use crate::page::{verify_checksum, Page, PageId};
+use crate::storagemanager::fileops::{LargeFile, ManagedFile};
+use crate::storagemanager::serialization::Serializable;
+
+
+struct BufferPool<F: LargeFile = ManagedFile> {
+    file: F,
+    page_size: usize,
+    capacity: usize, // Maximum number of pages kept in memory
+    pages: HashMap<PageId, Page>,
+    lru: VecDeque<PageId>, // Least recently used page at the front
+    dirty: HashSet<PageId>, // Pages modified since they were read
+}
+
+impl<F: LargeFile> BufferPool<F> {
+    fn new(file: F, page_size: usize, capacity: usize) -> Self {
+        assert!(capacity > 0, "The buffer pool needs room for at least one page");
+        BufferPool {
+            file,
+            page_size,
+            capacity,
+            pages: HashMap::new(),
+            lru: VecDeque::new(),
+            dirty: HashSet::new(),
+        }
+    }
+
+    // Returns the page, reading it from disk if it is not in memory
+    fn fetch(&mut self, page_id: PageId) -> Result<&Page> {
+        self.load(&page_id)?;
+        Ok(&self.pages[&page_id])
+    }
+
+    // Same as fetch, but the page is marked dirty so it is written back later
+    fn fetch_mut(&mut self, page_id: PageId) -> Result<&mut Page> {
+        self.load(&page_id)?;
+        self.dirty.insert(page_id.clone());
+        Ok(self.pages.get_mut(&page_id).unwrap())
+    }
+
+    // Adds a new page to the pool. It is dirty until it reaches the disk.
+    fn put(&mut self, page_id: PageId, page: Page) -> Result<()> {
+        if !self.pages.contains_key(&page_id) {
+            self.make_room()?;
+        }
+        self.pages.insert(page_id.clone(), page);
+        self.touch(&page_id);
+        self.dirty.insert(page_id);
+        Ok(())
+    }
+
+    // Writes every dirty page to disk, keeping them in memory
+    // If a write fails, the pages not written yet stay dirty
+    fn flush_all(&mut self) -> Result<()> {
+        let dirty: Vec<PageId> = self.dirty.iter().cloned().collect();
+        for page_id in dirty {
+            self.file.write_page(page_id.as_int() as u64, &self.pages[&page_id].serialize())?;
+            self.dirty.remove(&page_id);
+        }
+        Ok(())
+    }
+
+    fn load(&mut self, page_id: &PageId) -> Result<()> {
+        if !self.pages.contains_key(page_id) {
+            let buffer = self.file.read_page(page_id.as_int() as u64, self.page_size)?;
+            // A zero-filled or corrupt page would make Page::deserialize panic
+            if !verify_checksum(&buffer) {
+                return Err(Error::new(ErrorKind::InvalidData, format!("Page {} failed its checksum", page_id)));
+            }
+            let page = Page::deserialize(&buffer, &mut 0);
+            self.make_room()?;
+            self.pages.insert(page_id.clone(), page);
+        }
+        self.touch(page_id);
+        Ok(())
+    }
+
+    // Moves the page to the most recently used end
+    fn touch(&mut self, page_id: &PageId) {
+        self.lru.retain(|id| id != page_id);
+        self.lru.push_back(page_id.clone());
+    }
+
+    // Evicts the least recently used page when the pool is full
+    // A dirty victim is only dropped once it has been written back
+    fn make_room(&mut self) -> Result<()> {
+        if self.pages.len() < self.capacity {
+            return Ok(());
+        }
+        let victim = self.lru.front().unwrap().clone();
+        if self.dirty.contains(&victim) {
+            self.file.write_page(victim.as_int() as u64, &self.pages[&victim].serialize())?;
+            self.dirty.remove(&victim);
+        }
+        self.lru.pop_front();
+        self.pages.remove(&victim);
+        Ok(())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storagemanager::serialization::DataType;
+    use std::cell::Cell;
+    use std::io::Error;
+
+    const PAGE_SIZE: i32 = 256;
+
+    // File whose writes fail while `fail` is set, and are discarded otherwise
+    struct FailingFile {
+        fail: Cell<bool>,
+    }
+
+    impl LargeFile for FailingFile {
+        fn read(&self, _offset: usize, _size: usize) -> Result<Vec<u8>> {
+            Err(Error::other("nothing to read"))
+        }
+
+        fn write(&self, _offset: usize, _buf: &[u8]) -> Result<()> {
+            if self.fail.get() {
+                return Err(Error::other("disk full"));
+            }
+            Ok(())
+        }
+    }
+
+    fn temp_pool(name: &str, capacity: usize) -> (BufferPool, String) {
+        let path = std::env::temp_dir().join(name);
+        let _ = std::fs::remove_file(&path);
+        let path = path.to_str().unwrap().to_string();
+        (BufferPool::new(ManagedFile::new(&path), PAGE_SIZE as usize, capacity), path)
+    }
+
+    #[test]
+    fn test_miss_loads_from_disk() {
+        let (mut pool, path) = temp_pool("rustgresql_test_pool_miss.db", 2);
+        let page = Page::empty(DataType::Int32(1), PAGE_SIZE);
+        ManagedFile::new(&path).write_page(1, &page.serialize()).unwrap();
+
+        let fetched = pool.fetch(DataType::Int32(1)).unwrap();
+        assert_eq!(fetched.serialize(), page.serialize());
+        assert!(pool.fetch(DataType::Int32(5)).is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_corrupt_page_is_an_error() {
+        let (mut pool, path) = temp_pool("rustgresql_test_pool_corrupt.db", 2);
+        let file = ManagedFile::new(&path);
+        let mut corrupt = Page::empty(DataType::Int32(1), PAGE_SIZE).serialize();
+        corrupt[100] ^= 0xFF;
+        file.write_page(0, &vec![0; PAGE_SIZE as usize]).unwrap();
+        file.write_page(1, &corrupt).unwrap();
+
+        for page_id in 0..2 {
+            let error = pool.fetch(DataType::Int32(page_id)).unwrap_err();
+            assert_eq!(error.kind(), ErrorKind::InvalidData);
+        }
+        assert!(pool.pages.is_empty());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_hit_does_not_read_disk() {
+        let (mut pool, path) = temp_pool("rustgresql_test_pool_hit.db", 2);
+        let page = Page::empty(DataType::Int32(0), PAGE_SIZE);
+        ManagedFile::new(&path).write_page(0, &page.serialize()).unwrap();
+        pool.fetch(DataType::Int32(0)).unwrap();
+
+        // Once cached, the page is served even if the file is gone
+        std::fs::remove_file(&path).unwrap();
+        assert!(pool.fetch(DataType::Int32(0)).is_ok());
+
+        // Borrowing the page mutably marks it dirty
+        assert!(pool.dirty.is_empty());
+        pool.fetch_mut(DataType::Int32(0)).unwrap();
+        assert!(pool.dirty.contains(&DataType::Int32(0)));
+    }
+
+    #[test]
+    fn test_eviction_writes_back_dirty_pages() {
+        let (mut pool, path) = temp_pool("rustgresql_test_pool_evict.db", 2);
+        for i in 0..3 {
+            pool.put(DataType::Int32(i), Page::empty(DataType::Int32(i), PAGE_SIZE)).unwrap();
+        }
+        assert_eq!(pool.pages.len(), 2);
+        assert!(!pool.pages.contains_key(&DataType::Int32(0)));
+
+        // The evicted page reached the disk and can be fetched again
+        let file = ManagedFile::new(&path);
+        let on_disk = file.read_page(0, PAGE_SIZE as usize).unwrap();
+        assert_eq!(on_disk, Page::empty(DataType::Int32(0), PAGE_SIZE).serialize());
+        assert!(pool.fetch(DataType::Int32(0)).is_ok());
+        assert!(!pool.pages.contains_key(&DataType::Int32(1)));
+
+        pool.flush_all().unwrap();
+        assert!(pool.dirty.is_empty());
+        assert!(file.read_page(2, PAGE_SIZE as usize).is_ok());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_failed_writes_keep_pages_dirty() {
+        let mut pool = BufferPool::new(FailingFile { fail: Cell::new(true) }, PAGE_SIZE as usize, 2);
+        for i in 0..2 {
+            pool.put(DataType::Int32(i), Page::empty(DataType::Int32(i), PAGE_SIZE)).unwrap();
+        }
+
+        // Nothing reached the disk, so every page is still dirty
+        assert!(pool.flush_all().is_err());
+        assert_eq!(pool.dirty.len(), 2);
+
+        // The victim cannot be written back, so it is not evicted
+        assert!(pool.put(DataType::Int32(2), Page::empty(DataType::Int32(2), PAGE_SIZE)).is_err());
+        assert_eq!(pool.pages.len(), 2);
+        assert!(pool.pages.contains_key(&DataType::Int32(0)));
+        assert!(pool.dirty.contains(&DataType::Int32(0)));
+
+        // Once the disk recovers, the same pages can be flushed
+        pool.file.fail.set(false);
+        pool.flush_all().unwrap();
+        assert!(pool.dirty.is_empty());
+        pool.put(DataType::Int32(2), Page::empty(DataType::Int32(2), PAGE_SIZE)).unwrap();
+        assert!(!pool.pages.contains_key(&DataType::Int32(0)));
+    }
+}
@@ -1,23 +1,79 @@
 // module catalog
 // src/catalog.rs
 // This module contains the implementation of the database catalog.
+use std::io::{self, Write};
 use crate::storagemanager::serialization::{DataType, Serializable};
-use crate::storagemanager::fileops::{ManagedFile, SmallFile};
+use crate::storagemanager::fileops::{check_file_header, file_header, ManagedFile, SmallFile};
+use crate::page::Page;
 
 
 pub type ObjectId = DataType;
 
 
+// Comments are optional, so they are stored as a Varchar when present and as Null otherwise.
+fn serialize_comment(comment: &Option<String>) -> Vec<u8> {
+    match comment {
+        Some(text) => DataType::Varchar(text.clone()).serialize(),
+        None => DataType::Null.serialize(),
+    }
+}
+
+fn deserialize_comment(serialized: &[u8], offset: &mut usize) -> Option<String> {
+    match DataType::deserialize(serialized, offset) {
+        DataType::Null => None,
+        comment => Some(comment.as_string()),
+    }
+}
+
+
 /// Column of a table
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 /// Represents a column in a database table.
 struct Column {
     oid: ObjectId,
     name: DataType,
+    dtype: DataType, // Declared type of the column, stored as a zero value of that type (e.g. Int32(0))
     max_value: DataType,
     min_value: DataType,
     constraints: Vec<Constraint>,
+    comment: Option<String>, // Set by COMMENT ON COLUMN
+
+}
 
+
+impl Column {
+    // Widens the min/max statistics to include an inserted value.
+    // NULLs are not part of the range, and a NULL bound means no value has been seen yet.
+    fn observe(&mut self, value: &DataType) {
+        if *value == DataType::Null {
+            return;
+        }
+        if self.min_value == DataType::Null || *value < self.min_value {
+            self.min_value = value.clone();
+        }
+        if self.max_value == DataType::Null || *value > self.max_value {
+            self.max_value = value.clone();
+        }
+    }
+}
+
+
+// Quotes a string for JSON, escaping quotes, backslashes and control characters
+fn json_string(text: &str) -> String {
+    let mut escaped = String::from("\"");
+    for c in text.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if c.is_control() => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped.push('"');
+    escaped
 }
 
 
@@ -28,19 +84,23 @@ impl Serializable for Column {
         let mut serialized = Vec::new();
         serialized.extend(self.oid.serialize());
         serialized.extend(self.name.serialize());       
+        serialized.extend(self.dtype.serialize());
         serialized.extend(self.max_value.serialize());
         serialized.extend(self.min_value.serialize());
         serialized.extend(Constraint::serialize_list(&self.constraints));
+        serialized.extend(serialize_comment(&self.comment));
         serialized
     }
 
     fn deserialize(serialized: &[u8], offset: &mut usize) -> Self {
         let oid = DataType::deserialize(serialized, offset);
         let name = DataType::deserialize(serialized, offset);
+        let dtype = DataType::deserialize(serialized, offset);
         let max_value = DataType::deserialize(serialized, offset);
         let min_value = DataType::deserialize(serialized, offset);
         let constraints = Constraint::deserialize_list(serialized, offset);
-        Column { oid, name, max_value, min_value, constraints }
+        let comment = deserialize_comment(serialized, offset);
+        Column { oid, name, dtype, max_value, min_value, constraints, comment }
     
 
 }
@@ -51,7 +111,7 @@ impl Serializable for Column {
 // An index of a table.
 // For now the attribute columns is a list of strings, but it should be a list of columns in the future. 
 // As the column is Serializable, the index can be serialized and deserialized.
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 struct Index {
     oid: ObjectId,
     name: DataType,
@@ -82,13 +142,68 @@ impl Serializable for Index {
 // A table in a database.
 // A table has a name, a list of columns and a list of indexes.
 // This is my implementation of a TableSchema, which would just be a list of this tables.
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 struct Table {
     oid: ObjectId,
     name: DataType,
     columns: Vec<Column>,
     indexes: Vec<Index>,
+    primary_key: Vec<ObjectId>, // Oids of the columns forming the primary key, empty if there is none
+    comment: Option<String>, // Set by COMMENT ON TABLE
+
+}
+
+impl Table {
+    fn has_name(&self, name: &str) -> bool {
+        matches!(&self.name, DataType::Varchar(table_name) if table_name == name)
+    }
+
+    // Sets the primary key, checking that every oid belongs to a column of the table
+    fn set_primary_key(&mut self, oids: Vec<ObjectId>) -> Result<(), String> {
+        for oid in &oids {
+            if !self.columns.iter().any(|c| c.oid == *oid) {
+                return Err(format!("Column {} does not exist in table {}", oid.as_string(), self.name.as_string()));
+            }
+        }
+        self.primary_key = oids;
+        Ok(())
+    }
+
+    fn add_column(&mut self, col: Column) {
+        self.columns.push(col);
+    }
+
+    // Removes a column by name and returns it.
+    // Indexes over the column are dropped with it, as is the column's part of the primary key.
+    fn remove_column(&mut self, name: &str) -> Option<Column> {
+        let position = self.columns.iter()
+            .position(|c| matches!(&c.name, DataType::Varchar(column_name) if column_name == name))?;
+        let column = self.columns.remove(position);
+        self.indexes.retain(|index| !index.columns.contains(&column.name));
+        self.primary_key.retain(|oid| *oid != column.oid);
+        Some(column)
+    }
+
+    fn get_index(&self, name: &str) -> Option<&Index> {
+        self.indexes.iter()
+            .find(|index| matches!(&index.name, DataType::Varchar(index_name) if index_name == name))
+    }
+
+    // Finds an index usable to look up the given columns.
+    // An index matches when its columns equal the requested ones or are a prefix of them;
+    // an exact match wins, otherwise the longest prefix is returned.
+    fn find_index_for_columns(&self, columns: &[DataType]) -> Option<&Index> {
+        self.indexes.iter()
+            .filter(|index| !index.columns.is_empty() && columns.starts_with(&index.columns))
+            .max_by_key(|index| index.columns.len())
+    }
 
+    // Columns of the primary key, in key order
+    fn primary_key_columns(&self) -> Vec<&Column> {
+        self.primary_key.iter()
+            .filter_map(|oid| self.columns.iter().find(|c| c.oid == *oid))
+            .collect()
+    }
 }
 
 impl Serializable for Table {
@@ -98,6 +213,8 @@ impl Serializable for Table {
         serialized.extend(self.name.serialize());
         serialized.extend(Column::serialize_list(&self.columns));
         serialized.extend(Index::serialize_list(&self.indexes));
+        serialized.extend(DataType::serialize_list(&self.primary_key));
+        serialized.extend(serialize_comment(&self.comment));
   
         serialized
     }
@@ -107,21 +224,81 @@ impl Serializable for Table {
         let name = DataType::deserialize(serialized, offset);
         let columns = Column::deserialize_list(serialized, offset);
         let indexes = Index::deserialize_list(serialized, offset);
+        let primary_key = DataType::deserialize_list(serialized, offset);
+        let comment = deserialize_comment(serialized, offset);
     
         
-        Table {oid, name, columns, indexes}
+        Table {oid, name, columns, indexes, primary_key, comment}
+    }
+
+    // Streams the table field by field, in the same layout as serialize
+    fn serialize_into<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        self.oid.serialize_into(w)?;
+        self.name.serialize_into(w)?;
+        Column::serialize_list_into(&self.columns, w)?;
+        Index::serialize_list_into(&self.indexes, w)?;
+        DataType::serialize_list_into(&self.primary_key, w)?;
+        w.write_all(&serialize_comment(&self.comment))
     }
 }
 
 
-// Easy implementation of a constraint
-// A constraint has a name and a type.
-// This is kept easy for now as we are starting the development.
-#[derive(Debug)]
+// Kind of a constraint
+#[derive(Debug, Clone, PartialEq)]
+enum ConstraintKind {
+    NotNull,
+    Unique,
+    PrimaryKey,
+    Check(String), // Text of the CHECK expression
+    ForeignKey { table: DataType, column: DataType }, // Referenced table and column names
+}
+
+// A kind is serialized as a discriminant byte followed by its fields, if any
+impl Serializable for ConstraintKind {
+    fn serialize(&self) -> Vec<u8> {
+        let mut serialized = Vec::new();
+        match self {
+            ConstraintKind::NotNull => serialized.push(0x01),
+            ConstraintKind::Unique => serialized.push(0x02),
+            ConstraintKind::PrimaryKey => serialized.push(0x03),
+            ConstraintKind::Check(expression) => {
+                serialized.push(0x04);
+                serialized.extend(DataType::Varchar(expression.clone()).serialize());
+            }
+            ConstraintKind::ForeignKey { table, column } => {
+                serialized.push(0x05);
+                serialized.extend(table.serialize());
+                serialized.extend(column.serialize());
+            }
+        }
+        serialized
+    }
+
+    fn deserialize(serialized: &[u8], offset: &mut usize) -> Self {
+        let discriminant = serialized[*offset];
+        *offset += 1;
+        match discriminant {
+            0x01 => ConstraintKind::NotNull,
+            0x02 => ConstraintKind::Unique,
+            0x03 => ConstraintKind::PrimaryKey,
+            0x04 => ConstraintKind::Check(DataType::deserialize(serialized, offset).as_string()),
+            0x05 => {
+                let table = DataType::deserialize(serialized, offset);
+                let column = DataType::deserialize(serialized, offset);
+                ConstraintKind::ForeignKey { table, column }
+            }
+            _ => panic!("Unknown constraint kind: {}", discriminant),
+        }
+    }
+}
+
+
+// A constraint has a name and a kind.
+#[derive(Debug, PartialEq)]
 struct Constraint {
     oid: ObjectId,
     name: DataType,
-    dtype: DataType,
+    kind: ConstraintKind,
 
 }
 
@@ -130,15 +307,15 @@ impl Serializable for Constraint {
         let mut serialized = Vec::new();
         serialized.extend(self.oid.serialize());
         serialized.extend(self.name.serialize());
-        serialized.extend(self.dtype.serialize());
+        serialized.extend(self.kind.serialize());
         serialized
     }
 
     fn deserialize(serialized: &[u8], offset: &mut usize) -> Self {
         let oid = DataType::deserialize(serialized, offset);
         let name = DataType::deserialize(serialized, offset);
-        let dtype = DataType::deserialize(serialized, offset);
-        Constraint { oid, name, dtype}
+        let kind = ConstraintKind::deserialize(serialized, offset);
+        Constraint { oid, name, kind}
     }
 }
 
@@ -151,6 +328,7 @@ struct DataCatalog {
     file: ManagedFile, // This is the file where the data catalog is stored, it should be a ManagedFile
     // Implements the SmallFile trait so it can be read and written into memory
     tables: Vec<Table>, // Schema of the database
+    next_oid: i32, // Next object id to hand out, persisted with the catalog
 
 }
 
@@ -162,32 +340,380 @@ impl DataCatalog {
         DataCatalog {
             file,
             tables: Vec::new(),
+            next_oid: 1,
         }
     }
 
+    // Returns a fresh object id for a new table, column, index or constraint
+    fn allocate_oid(&mut self) -> ObjectId {
+        let oid = DataType::Int32(self.next_oid);
+        self.next_oid += 1;
+        oid
+    }
+
     fn set_file(&mut self, path: String) {
         self.file = ManagedFile::new(&path);
     }
 
-    fn add_table(&mut self, table: Table) {
+    // Writes the catalog to its file
+    fn save(&self) -> io::Result<()> {
+        self.file.write_all(&self.serialize())
+    }
+
+    // Reads a catalog back from the file at path, which becomes its file
+    fn load(path: &str) -> io::Result<DataCatalog> {
+        let file = ManagedFile::new(path);
+        let buffer = file.read_to_end()?;
+        check_file_header(&buffer, &mut 0)?;
+        let mut catalog = DataCatalog::deserialize(&buffer, &mut 0);
+        catalog.file = file;
+        Ok(catalog)
+    }
+
+    // Adds a table to the catalog.
+    // Table names and oids must be unique, so get_table always finds a single table.
+    fn add_table(&mut self, table: Table) -> Result<(), String> {
+        if self.tables.iter().any(|t| t.name == table.name) {
+            return Err(format!("Table {} already exists", table.name.as_string()));
+        }
+        if self.tables.iter().any(|t| t.oid == table.oid) {
+            return Err(format!("Object id {} is already in use", table.oid.as_string()));
+        }
+        // Oids picked by hand, for the table or any of its objects, must not be handed out again by allocate_oid
+        let oids = std::iter::once(&table.oid)
+            .chain(table.columns.iter().flat_map(|c| std::iter::once(&c.oid).chain(c.constraints.iter().map(|k| &k.oid))))
+            .chain(table.indexes.iter().map(|i| &i.oid));
+        if let Some(max) = oids.filter_map(|oid| match oid { DataType::Int32(oid) => Some(*oid), _ => None }).max() {
+            self.next_oid = self.next_oid.max(max.saturating_add(1));
+        }
         self.tables.push(table);
+        Ok(())
+    }
+
+    // Looks a table up by name.
+    // Names are case sensitive: the parser is expected to normalize them before they reach the catalog.
+    fn get_table(&self, name: &str) -> Option<&Table> {
+        self.tables.iter().find(|t| t.has_name(name))
+    }
+
+    fn get_table_mut(&mut self, name: &str) -> Option<&mut Table> {
+        self.tables.iter_mut().find(|t| t.has_name(name))
+    }
+
+    // Removes the table with the given name and returns it, if it exists
+    fn drop_table(&mut self, name: &str) -> Option<Table> {
+        let position = self.tables.iter().position(|t| t.has_name(name))?;
+        Some(self.tables.remove(position))
+    }
+
+    // Renames a table, keeping its oid, columns and indexes
+    fn rename_table(&mut self, old: &str, new: &str) -> Result<(), String> {
+        if self.get_table(new).is_some() {
+            return Err(format!("Table {} already exists", new));
+        }
+        let table = self.get_table_mut(old).ok_or(format!("Table {} does not exist", old))?;
+        table.name = DataType::Varchar(new.to_string());
+        Ok(())
+    }
+
+    // Describes the schema as JSON, for tools that cannot read the binary format.
+    // Only the schema is exported: oids and column statistics are left out.
+    fn to_json(&self) -> String {
+        let tables: Vec<String> = self.tables.iter().map(|table| {
+            let columns: Vec<String> = table.columns.iter().map(|column| {
+                let constraints: Vec<String> = column.constraints.iter().map(|constraint| {
+                    let kind = match &constraint.kind {
+                        ConstraintKind::NotNull => "\"kind\":\"NOT NULL\"".to_string(),
+                        ConstraintKind::Unique => "\"kind\":\"UNIQUE\"".to_string(),
+                        ConstraintKind::PrimaryKey => "\"kind\":\"PRIMARY KEY\"".to_string(),
+                        ConstraintKind::Check(expression) => format!("\"kind\":\"CHECK\",\"expression\":{}", json_string(expression)),
+                        ConstraintKind::ForeignKey { table, column } => format!("\"kind\":\"FOREIGN KEY\",\"table\":{},\"column\":{}",
+                            json_string(&table.as_string()), json_string(&column.as_string())),
+                    };
+                    format!("{{\"name\":{},{}}}", json_string(&constraint.name.as_string()), kind)
+                }).collect();
+                format!("{{\"name\":{},\"type\":{},\"constraints\":[{}]}}",
+                    json_string(&column.name.as_string()), json_string(column.dtype.type_name()), constraints.join(","))
+            }).collect();
+            let indexes: Vec<String> = table.indexes.iter().map(|index| {
+                let index_columns: Vec<String> = index.columns.iter().map(|column| json_string(&column.as_string())).collect();
+                format!("{{\"name\":{},\"columns\":[{}],\"unique\":{}}}",
+                    json_string(&index.name.as_string()), index_columns.join(","), index.unique == DataType::Bool(true))
+            }).collect();
+            format!("{{\"name\":{},\"columns\":[{}],\"indexes\":[{}]}}",
+                json_string(&table.name.as_string()), columns.join(","), indexes.join(","))
+        }).collect();
+        format!("{{\"tables\":[{}]}}", tables.join(","))
+    }
+}
+
+
+// Builds a table step by step instead of writing the nested literals by hand.
+// Oids are only handed out by build, from the catalog the table is meant for:
+// first the table, then the columns and then the indexes, in the order they were added.
+#[derive(Debug, Default)]
+struct TableBuilder {
+    name: String,
+    columns: Vec<(String, DataType)>,
+    indexes: Vec<(String, Vec<String>, bool)>,
+}
+
+impl TableBuilder {
+    fn new() -> Self {
+        TableBuilder::default()
+    }
+
+    fn name(mut self, name: &str) -> Self {
+        self.name = name.to_string();
+        self
+    }
+
+    // The type is given as a zero value, as in Column::dtype
+    fn column(mut self, name: &str, dtype: DataType) -> Self {
+        self.columns.push((name.to_string(), dtype));
+        self
+    }
+
+    fn index(mut self, name: &str, columns: &[&str], unique: bool) -> Self {
+        self.indexes.push((name.to_string(), columns.iter().map(|column| column.to_string()).collect(), unique));
+        self
+    }
+
+    fn build(self, catalog: &mut DataCatalog) -> Table {
+        let oid = catalog.allocate_oid();
+        let columns = self.columns.into_iter().map(|(name, dtype)| Column {
+            oid: catalog.allocate_oid(),
+            name: DataType::Varchar(name),
+            dtype,
+            max_value: DataType::Null,
+            min_value: DataType::Null,
+            constraints: vec![],
+            comment: None,
+        }).collect();
+        let indexes = self.indexes.into_iter().map(|(name, columns, unique)| Index {
+            oid: catalog.allocate_oid(),
+            name: DataType::Varchar(name),
+            columns: columns.into_iter().map(DataType::Varchar).collect(),
+            unique: DataType::Bool(unique),
+        }).collect();
+        Table {
+            oid,
+            name: DataType::Varchar(self.name),
+            columns,
+            indexes,
+            primary_key: vec![],
+            comment: None,
+        }
+    }
+}
+
+
+// Checks that a tuple can be stored in the table: one value per column, each of the column's declared type.
+// NULL is accepted for any column that is not NOT NULL or part of the primary key.
+fn validate_tuple(table: &Table, values: &[DataType]) -> Result<(), String> {
+    if values.len() != table.columns.len() {
+        return Err(format!("Table {} has {} columns but {} values were given", table.name.as_string(), table.columns.len(), values.len()));
+    }
+    for (column, value) in table.columns.iter().zip(values) {
+        if *value == DataType::Null {
+            let not_null = table.primary_key.contains(&column.oid)
+                || column.constraints.iter().any(|c| matches!(c.kind, ConstraintKind::NotNull | ConstraintKind::PrimaryKey));
+            if not_null {
+                return Err(format!("Column {} cannot be NULL", column.name.as_string()));
+            }
+        } else if value.get_type() != column.dtype.get_type() {
+            return Err(format!("Column {} expects {} but got {}", column.name.as_string(), column.dtype.type_name(), value.type_name()));
+        }
+    }
+    Ok(())
+}
+
+
+// Splits a CSV line into its fields.
+// Fields may be wrapped in double quotes to hold commas, with "" standing for a quote inside them.
+// Each field comes with whether it was quoted, so an empty quoted field can be told apart from a missing one.
+fn split_csv_line(line: &str) -> Result<Vec<(String, bool)>, String> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut in_quotes = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if in_quotes && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' if in_quotes => in_quotes = false,
+            '"' if field.is_empty() && !quoted => {
+                in_quotes = true;
+                quoted = true;
+            }
+            ',' if !in_quotes => {
+                fields.push((std::mem::take(&mut field), quoted));
+                quoted = false;
+            }
+            _ => field.push(c),
+        }
+    }
+    if in_quotes {
+        return Err(format!("Unterminated quoted field in line: {}", line));
+    }
+    fields.push((field, quoted));
+    Ok(fields)
+}
+
+// Parses YYYY-MM-DD into its components
+// They are not checked here: parse_csv_field builds the value with DataType::try_date, which rejects dates that do not exist
+fn parse_date(text: &str) -> Option<(i32, u32, u32)> {
+    let mut parts = text.splitn(3, '-');
+    Some((parts.next()?.parse().ok()?, parts.next()?.parse().ok()?, parts.next()?.parse().ok()?))
+}
+
+// Parses the text of a CSV field into a value of the column type.
+// An empty unquoted field is NULL.
+fn parse_csv_field(column: &Column, text: &str, quoted: bool) -> Result<DataType, String> {
+    if text.is_empty() && !quoted {
+        return Ok(DataType::Null);
+    }
+    let invalid = || format!("Invalid {} value for column {}: {}", column.dtype.type_name(), column.name.as_string(), text);
+    let value = match column.dtype {
+        DataType::Varchar(_) => DataType::Varchar(text.to_string()),
+        DataType::Int32(_) => DataType::Int32(text.trim().parse().map_err(|_| invalid())?),
+        DataType::Int64(_) => DataType::Int64(text.trim().parse().map_err(|_| invalid())?),
+        DataType::Float64(_) => DataType::Float64(text.trim().parse().map_err(|_| invalid())?),
+        DataType::Bool(_) => match text.trim().to_uppercase().as_str() {
+            "TRUE" => DataType::Bool(true),
+            "FALSE" => DataType::Bool(false),
+            _ => return Err(invalid()),
+        },
+        DataType::Date(_) => {
+            let (year, month, day) = parse_date(text.trim()).ok_or_else(invalid)?;
+            DataType::try_date(year, month, day).map_err(|_| invalid())?
+        }
+        DataType::Decimal { .. } => DataType::decimal(text.trim())?,
+        DataType::Uuid(_) => DataType::uuid(text.trim())?,
+        _ => return Err(format!("Cannot import values of type {} for column {}", column.dtype.type_name(), column.name.as_string())),
+    };
+    Ok(value)
+}
+
+// Appends every row of a CSV file to the page, parsing the fields with the types of the table columns.
+// The first line is a header and is skipped, as are blank lines.
+// Every row is parsed and validated before any is stored, so a bad file leaves the page untouched.
+// Returns the number of rows imported.
+fn import_csv(path: &str, page: &mut Page, schema: &Table) -> Result<usize, String> {
+    let buffer = ManagedFile::new(path).read_to_end().map_err(|e| format!("Cannot read {}: {}", path, e))?;
+    let content = String::from_utf8(buffer).map_err(|_| format!("{} is not valid UTF-8", path))?;
+    let mut rows = Vec::new();
+    for (number, line) in content.lines().enumerate().skip(1) {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let fields = split_csv_line(line)?;
+        if fields.len() != schema.columns.len() {
+            return Err(format!("Line {} has {} fields but table {} has {} columns", number + 1, fields.len(), schema.name.as_string(), schema.columns.len()));
+        }
+        let values = schema.columns.iter().zip(&fields)
+            .map(|(column, (text, quoted))| parse_csv_field(column, text, *quoted))
+            .collect::<Result<Vec<DataType>, String>>()?;
+        validate_tuple(schema, &values).map_err(|e| format!("Line {}: {}", number + 1, e))?;
+        rows.push(values);
     }
+    if !page.can_fit_all(&rows) {
+        return Err(format!("The {} rows of {} do not fit in the page", rows.len(), path));
+    }
+    let count = rows.len();
+    for values in rows {
+        page.append_tuple(values);
+    }
+    Ok(count)
+}
+
+
+// A single difference between two versions of the catalog.
+// Tables and columns are matched by name, so a rename shows up as a drop plus an add.
+// This is the input from which migration DDL can be generated.
+#[derive(Debug, PartialEq)]
+enum SchemaChange {
+    AddTable { table: DataType },
+    DropTable { table: DataType },
+    AddColumn { table: DataType, column: DataType },
+    DropColumn { table: DataType, column: DataType },
+    AlterColumn { table: DataType, column: DataType },
+}
+
+
+// Two columns with the same name are considered altered when their type or constraints differ.
+fn column_altered(old: &Column, new: &Column) -> bool {
+    old.dtype.get_type() != new.dtype.get_type()
+        || old.constraints.len() != new.constraints.len()
+        || old.constraints.iter().zip(&new.constraints).any(|(a, b)| a.name != b.name || a.kind != b.kind)
+}
+
+
+// Computes the list of changes needed to go from the old catalog to the new one.
+// Dropped tables come first, followed by the changes of every table of the new catalog in order.
+fn diff_catalog(old: &DataCatalog, new: &DataCatalog) -> Vec<SchemaChange> {
+    let mut changes = Vec::new();
+
+    for old_table in &old.tables {
+        if !new.tables.iter().any(|t| t.name == old_table.name) {
+            changes.push(SchemaChange::DropTable { table: old_table.name.clone() });
+        }
+    }
+
+    for new_table in &new.tables {
+        let old_table = match old.tables.iter().find(|t| t.name == new_table.name) {
+            Some(table) => table,
+            None => {
+                changes.push(SchemaChange::AddTable { table: new_table.name.clone() });
+                continue;
+            }
+        };
+
+        for old_column in &old_table.columns {
+            if !new_table.columns.iter().any(|c| c.name == old_column.name) {
+                changes.push(SchemaChange::DropColumn { table: new_table.name.clone(), column: old_column.name.clone() });
+            }
+        }
+
+        for new_column in &new_table.columns {
+            match old_table.columns.iter().find(|c| c.name == new_column.name) {
+                Some(old_column) if column_altered(old_column, new_column) => {
+                    changes.push(SchemaChange::AlterColumn { table: new_table.name.clone(), column: new_column.name.clone() });
+                }
+                Some(_) => {}
+                None => {
+                    changes.push(SchemaChange::AddColumn { table: new_table.name.clone(), column: new_column.name.clone() });
+                }
+            }
+        }
+    }
+
+    changes
 }
 
 
 // The DataCatalog can be serialized and deserialized
 impl Serializable for DataCatalog {
     fn serialize(&self) -> Vec<u8> {
-        let mut serialized = Vec::new();
+        let mut serialized = file_header();
+        serialized.extend(DataType::Int32(self.next_oid).serialize());
         serialized.extend(Table::serialize_list(&self.tables));
         serialized
     }
 
     fn deserialize(serialized: &[u8], offset: &mut usize) -> Self {
+        if let Err(e) = check_file_header(serialized, offset) {
+            panic!("Cannot read the data catalog: {}", e);
+        }
+        let next_oid = DataType::deserialize(serialized, offset).as_int();
         let tables = Table::deserialize_list(serialized, offset);
         DataCatalog {
             file: ManagedFile::new("data/catalog.db"),
             tables,
+            next_oid,
         }
     }
 }
@@ -206,13 +732,15 @@ mod tests {
             name: DataType::Varchar("table".to_string()),
             columns: vec![Column { oid: DataType::Int32(2),
                 name: DataType::Varchar("column".to_string()),
+                dtype: DataType::Int32(0),
                 max_value: DataType::Int32(100),
                 min_value: DataType::Int32(0),
                 constraints: vec![Constraint {
                     oid: DataType::Int32(3),
                     name: DataType::Varchar("constraint".to_string()),
-                    dtype: DataType::Varchar("type".to_string()),
+                    kind: ConstraintKind::NotNull,
                 }],
+                comment: None,
             }],
             indexes: vec![Index {
                 oid: DataType::Int32(4),
@@ -220,8 +748,10 @@ mod tests {
                 columns: vec![DataType::Varchar("column".to_string())],
                 unique: DataType::Bool(true),
             }],
+            primary_key: vec![],
+            comment: None,
         };
-        data_catalog.add_table(table);
+        data_catalog.add_table(table).unwrap();
         let serialized = data_catalog.serialize();
         let mut offset = 0;
         let deserialized = DataCatalog::deserialize(&serialized, &mut offset);
@@ -231,6 +761,521 @@ mod tests {
 
 
 
+    // Helper to build a column without constraints
+    fn plain_column(oid: i32, name: &str) -> Column {
+        Column {
+            oid: DataType::Int32(oid),
+            name: DataType::Varchar(name.to_string()),
+            dtype: DataType::Int32(0),
+            max_value: DataType::Null,
+            min_value: DataType::Null,
+            constraints: vec![],
+            comment: None,
+        }
+    }
+
+    // Helper to build a table without indexes, primary key or comment
+    fn plain_table(oid: i32, name: &str, columns: Vec<Column>) -> Table {
+        Table {
+            oid: DataType::Int32(oid),
+            name: DataType::Varchar(name.to_string()),
+            columns,
+            indexes: vec![],
+            primary_key: vec![],
+            comment: None,
+        }
+    }
+
+    // Helper to build a catalog with the tables users (oid 1) and orders (oid 2), each with an id column
+    fn two_table_catalog() -> DataCatalog {
+        let mut catalog = DataCatalog::new("data/catalog.db".to_string());
+        catalog.add_table(plain_table(1, "users", vec![plain_column(11, "id")])).unwrap();
+        catalog.add_table(plain_table(2, "orders", vec![plain_column(12, "id")])).unwrap();
+        catalog
+    }
+
+    // Test that the diff reports an added table and a column added to an existing table
+    #[test]
+    fn test_diff_catalog() {
+        let mut old = DataCatalog::new("data/catalog.db".to_string());
+        old.add_table(plain_table(1, "users", vec![plain_column(2, "id")])).unwrap();
+
+        let mut new = DataCatalog::new("data/catalog.db".to_string());
+        new.add_table(plain_table(1, "users", vec![plain_column(2, "id"), plain_column(3, "email")])).unwrap();
+        new.add_table(plain_table(4, "orders", vec![plain_column(5, "id")])).unwrap();
+
+        let changes = diff_catalog(&old, &new);
+        assert_eq!(changes, vec![
+            SchemaChange::AddColumn { table: DataType::Varchar("users".to_string()), column: DataType::Varchar("email".to_string()) },
+            SchemaChange::AddTable { table: DataType::Varchar("orders".to_string()) },
+        ]);
+
+        // The reverse direction drops what was added
+        let changes = diff_catalog(&new, &old);
+        assert_eq!(changes, vec![
+            SchemaChange::DropTable { table: DataType::Varchar("orders".to_string()) },
+            SchemaChange::DropColumn { table: DataType::Varchar("users".to_string()), column: DataType::Varchar("email".to_string()) },
+        ]);
+        assert!(diff_catalog(&old, &old).is_empty());
+    }
+
+
+
+    // Test that table and column comments survive a serialization round trip
+    #[test]
+    fn test_comment_serialization() {
+        let mut column = plain_column(2, "email");
+        column.comment = Some("contact address".to_string());
+        let table = Table {
+            comment: Some("registered users".to_string()),
+            ..plain_table(1, "users", vec![column, plain_column(3, "id")])
+        };
+        let serialized = table.serialize();
+        let deserialized = Table::deserialize(&serialized, &mut 0);
+        assert_eq!(deserialized.comment, Some("registered users".to_string()));
+        assert_eq!(deserialized.columns[0].comment, Some("contact address".to_string()));
+        assert_eq!(deserialized.columns[1].comment, None);
+    }
+
+
+
+    // Test that the declared type of a column survives a round trip
+    #[test]
+    fn test_column_dtype() {
+        let id = plain_column(2, "id");
+        let mut email = plain_column(3, "email");
+        email.dtype = DataType::Varchar(String::new());
+        let table = plain_table(1, "users", vec![id, email]);
+        let deserialized = Table::deserialize(&table.serialize(), &mut 0);
+        assert_eq!(deserialized.columns[0].dtype, DataType::Int32(0));
+        assert_eq!(deserialized.columns[1].dtype, DataType::Varchar(String::new()));
+
+        // Changing the type of a column is reported as an alteration
+        assert!(column_altered(&deserialized.columns[0], &deserialized.columns[1]));
+    }
+
+
+
+    // Test that allocated oids are unique and the counter is persisted
+    #[test]
+    fn test_allocate_oid() {
+        let mut catalog = DataCatalog::new("data/catalog.db".to_string());
+        let first = catalog.allocate_oid();
+        let second = catalog.allocate_oid();
+        assert_ne!(first, second);
+
+        let mut deserialized = DataCatalog::deserialize(&catalog.serialize(), &mut 0);
+        let third = deserialized.allocate_oid();
+        assert!(third != first && third != second);
+        assert_eq!(third, DataType::Int32(3));
+    }
+
+    // Test that oids given by hand to a table and its objects are never allocated again
+    #[test]
+    fn test_add_table_advances_next_oid() {
+        let mut catalog = DataCatalog::new("data/catalog.db".to_string());
+        let mut age = plain_column(2, "age");
+        age.constraints.push(Constraint { oid: DataType::Int32(9), name: DataType::Varchar("age_not_null".to_string()), kind: ConstraintKind::NotNull });
+        catalog.add_table(Table {
+            indexes: vec![Index { oid: DataType::Int32(5), name: DataType::Varchar("users_age".to_string()), columns: vec![DataType::Int32(2)], unique: DataType::Bool(false) }],
+            ..plain_table(1, "users", vec![plain_column(3, "name"), age])
+        }).unwrap();
+        assert_eq!(catalog.allocate_oid(), DataType::Int32(10));
+
+        // Oids below the counter do not move it back
+        catalog.add_table(plain_table(4, "orders", vec![])).unwrap();
+        assert_eq!(catalog.allocate_oid(), DataType::Int32(11));
+    }
+
+
+
+    // Test single-column and composite primary keys
+    #[test]
+    fn test_primary_key() {
+        let mut table = plain_table(1, "order_lines", vec![plain_column(2, "order_id"), plain_column(3, "line"), plain_column(4, "amount")]);
+        table.set_primary_key(vec![DataType::Int32(2)]).unwrap();
+        let names: Vec<DataType> = table.primary_key_columns().iter().map(|c| c.name.clone()).collect();
+        assert_eq!(names, vec![DataType::Varchar("order_id".to_string())]);
+
+        table.set_primary_key(vec![DataType::Int32(2), DataType::Int32(3)]).unwrap();
+        let deserialized = Table::deserialize(&table.serialize(), &mut 0);
+        assert_eq!(deserialized.primary_key, vec![DataType::Int32(2), DataType::Int32(3)]);
+        assert_eq!(deserialized.primary_key_columns().len(), 2);
+
+        // Unknown columns are refused and the previous key is kept
+        assert!(table.set_primary_key(vec![DataType::Int32(99)]).is_err());
+        assert_eq!(table.primary_key.len(), 2);
+    }
+
+
+
+    // Test adding columns and removing one that is indexed
+    #[test]
+    fn test_add_remove_column() {
+        let mut table = Table {
+            indexes: vec![Index {
+                oid: DataType::Int32(4),
+                name: DataType::Varchar("users_email".to_string()),
+                columns: vec![DataType::Varchar("email".to_string())],
+                unique: DataType::Bool(true),
+            }],
+            ..plain_table(1, "users", vec![plain_column(2, "id")])
+        };
+        table.add_column(plain_column(3, "email"));
+        assert_eq!(table.columns.len(), 2);
+        assert_eq!(table.columns[1].name, DataType::Varchar("email".to_string()));
+
+        let removed = table.remove_column("email").unwrap();
+        assert_eq!(removed.oid, DataType::Int32(3));
+        assert_eq!(table.columns.len(), 1);
+        assert!(table.indexes.is_empty());
+        assert!(table.remove_column("email").is_none());
+    }
+
+
+
+    // Test finding indexes by name and by the columns they cover
+    #[test]
+    fn test_find_index() {
+        let index = |oid: i32, name: &str, columns: &[&str]| Index {
+            oid: DataType::Int32(oid),
+            name: DataType::Varchar(name.to_string()),
+            columns: columns.iter().map(|c| DataType::Varchar(c.to_string())).collect(),
+            unique: DataType::Bool(false),
+        };
+        let table = Table {
+            indexes: vec![index(5, "by_customer", &["customer"]), index(6, "by_customer_date", &["customer", "date"])],
+            ..plain_table(1, "orders", vec![plain_column(2, "customer"), plain_column(3, "date"), plain_column(4, "total")])
+        };
+        let columns = |names: &[&str]| -> Vec<DataType> { names.iter().map(|c| DataType::Varchar(c.to_string())).collect() };
+
+        assert_eq!(table.get_index("by_customer").unwrap().oid, DataType::Int32(5));
+        assert!(table.get_index("missing").is_none());
+
+        // Exact match
+        assert_eq!(table.find_index_for_columns(&columns(&["customer", "date"])).unwrap().oid, DataType::Int32(6));
+        // The index columns are a prefix of the requested ones
+        assert_eq!(table.find_index_for_columns(&columns(&["customer", "total"])).unwrap().oid, DataType::Int32(5));
+        // No index starts with this column
+        assert!(table.find_index_for_columns(&columns(&["total"])).is_none());
+    }
+
+
+
+    // Test that a saved catalog can be loaded back from its file
+    #[test]
+    fn test_save_load() {
+        let path = std::env::temp_dir().join("rustgresql_test_save_load.db");
+        let path = path.to_str().unwrap();
+        let mut catalog = DataCatalog::new(path.to_string());
+        catalog.add_table(plain_table(1, "users", vec![plain_column(2, "id")])).unwrap();
+        catalog.save().unwrap();
+
+        let loaded = DataCatalog::load(path).unwrap();
+        assert_eq!(loaded.tables.len(), 1);
+        assert_eq!(loaded.get_table("users").unwrap().columns[0].name, DataType::Varchar("id".to_string()));
+        assert_eq!(loaded.next_oid, catalog.next_oid);
+        assert_eq!(loaded.file.read_to_end().unwrap(), catalog.serialize());
+
+        assert!(DataCatalog::load("missing/catalog.db").is_err());
+
+        // A file in the layout used before the format header is rejected instead of misread
+        let header_size = file_header().len();
+        catalog.file.write_all(&catalog.serialize()[header_size..]).unwrap();
+        assert_eq!(DataCatalog::load(path).unwrap_err().kind(), io::ErrorKind::InvalidData);
+        std::fs::remove_file(path).unwrap();
+    }
+
+
+
+    // Test that a second table with the same name or oid is refused
+    #[test]
+    fn test_add_table_unique() {
+        let mut catalog = DataCatalog::new("data/catalog.db".to_string());
+        assert!(catalog.add_table(plain_table(1, "users", vec![])).is_ok());
+        assert!(catalog.add_table(plain_table(2, "orders", vec![])).is_ok());
+        assert!(catalog.add_table(plain_table(3, "users", vec![])).is_err());
+        assert!(catalog.add_table(plain_table(1, "products", vec![])).is_err());
+        assert_eq!(catalog.tables.len(), 2);
+    }
+
+
+
+    // Test that constraint kinds survive a round trip
+    #[test]
+    fn test_constraint_kinds() {
+        let mut column = plain_column(2, "customer_id");
+        column.constraints = vec![
+            Constraint { oid: DataType::Int32(3), name: DataType::Varchar("customer_not_null".to_string()), kind: ConstraintKind::NotNull },
+            Constraint {
+                oid: DataType::Int32(4),
+                name: DataType::Varchar("customer_fk".to_string()),
+                kind: ConstraintKind::ForeignKey {
+                    table: DataType::Varchar("customers".to_string()),
+                    column: DataType::Varchar("id".to_string()),
+                },
+            },
+            Constraint { oid: DataType::Int32(5), name: DataType::Varchar("positive".to_string()), kind: ConstraintKind::Check("customer_id > 0".to_string()) },
+        ];
+        let deserialized = Column::deserialize(&column.serialize(), &mut 0);
+        let kinds: Vec<ConstraintKind> = deserialized.constraints.iter().map(|c| c.kind.clone()).collect();
+        assert_eq!(kinds, vec![
+            ConstraintKind::NotNull,
+            ConstraintKind::ForeignKey {
+                table: DataType::Varchar("customers".to_string()),
+                column: DataType::Varchar("id".to_string()),
+            },
+            ConstraintKind::Check("customer_id > 0".to_string()),
+        ]);
+    }
+
+
+
+    // Test tuple validation against the table schema
+    #[test]
+    fn test_validate_tuple() {
+        let mut name = plain_column(3, "name");
+        name.dtype = DataType::Varchar(String::new());
+        name.constraints.push(Constraint { oid: DataType::Int32(4), name: DataType::Varchar("name_not_null".to_string()), kind: ConstraintKind::NotNull });
+        let table = plain_table(1, "users", vec![plain_column(2, "age"), name]);
+        assert!(validate_tuple(&table, &[DataType::Int32(30), DataType::Varchar("John".to_string())]).is_ok());
+        assert!(validate_tuple(&table, &[DataType::Null, DataType::Varchar("John".to_string())]).is_ok());
+        // Type mismatch
+        assert!(validate_tuple(&table, &[DataType::Varchar("30".to_string()), DataType::Varchar("John".to_string())]).is_err());
+        // Wrong arity
+        assert!(validate_tuple(&table, &[DataType::Int32(30)]).is_err());
+        // Null in a NOT NULL column
+        assert!(validate_tuple(&table, &[DataType::Int32(30), DataType::Null]).is_err());
+    }
+
+
+
+    // Test that min/max follow the observed values
+    #[test]
+    fn test_observe() {
+        let mut column = plain_column(2, "age");
+        for age in [30, 18, 45, 65, 40] {
+            column.observe(&DataType::Int32(age));
+        }
+        column.observe(&DataType::Null);
+        assert_eq!(column.min_value, DataType::Int32(18));
+        assert_eq!(column.max_value, DataType::Int32(65));
+    }
+
+
+
+    // Test that a CSV with a header and quoted fields is imported into a page
+    #[test]
+    fn test_import_csv() {
+        let mut name = plain_column(3, "name");
+        name.dtype = DataType::Varchar(String::new());
+        let table = plain_table(1, "users", vec![plain_column(2, "age"), name]);
+        let path = std::env::temp_dir().join("rustgresql_test_import.csv");
+        std::fs::write(&path, "age,name\n30,John\n25,\"Smith, Jane\"\n,\"Say \"\"hi\"\"\"\n").unwrap();
+
+        let mut page = Page::empty(DataType::Int32(1), 4096);
+        assert_eq!(import_csv(path.to_str().unwrap(), &mut page, &table), Ok(3));
+        assert_eq!(page.get_tuple(1), Some(&vec![DataType::Int32(30), DataType::Varchar("John".to_string())]));
+        assert_eq!(page.get_tuple(2), Some(&vec![DataType::Int32(25), DataType::Varchar("Smith, Jane".to_string())]));
+        assert_eq!(page.get_tuple(3), Some(&vec![DataType::Null, DataType::Varchar("Say \"hi\"".to_string())]));
+        assert_eq!(page.get_tuple(4), None);
+
+        // A row that does not match the schema is rejected
+        std::fs::write(&path, "age,name\nthirty,John\n").unwrap();
+        assert!(import_csv(path.to_str().unwrap(), &mut page, &table).is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+
+
+    // Test that a bad row in the middle of the file stops the import before anything is stored
+    #[test]
+    fn test_import_csv_all_or_nothing() {
+        let table = plain_table(1, "users", vec![plain_column(2, "age"), plain_column(3, "score")]);
+        let path = std::env::temp_dir().join("rustgresql_test_import_bad_row.csv");
+        let mut page = Page::empty(DataType::Int32(1), 4096);
+
+        std::fs::write(&path, "age,score\n30,1\n25,not a number\n40,3\n").unwrap();
+        assert!(import_csv(path.to_str().unwrap(), &mut page, &table).is_err());
+        assert_eq!(page.get_tuple(1), None);
+
+        // Rows that would not fit together are not stored either
+        let rows: String = (0..400).map(|i| format!("{},{}\n", i, i)).collect();
+        std::fs::write(&path, format!("age,score\n{}", rows)).unwrap();
+        assert!(import_csv(path.to_str().unwrap(), &mut page, &table).is_err());
+        assert_eq!(page.get_tuple(1), None);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    // Test that dates that do not exist are rejected instead of rolling over into another date
+    #[test]
+    fn test_import_csv_invalid_date() {
+        let mut born = plain_column(3, "born");
+        born.dtype = DataType::Date(0);
+        let table = plain_table(1, "users", vec![plain_column(2, "age"), born]);
+        let path = std::env::temp_dir().join("rustgresql_test_import_bad_date.csv");
+        let mut page = Page::empty(DataType::Int32(1), 4096);
+
+        for bad_date in ["2024-13-45", "2024-02-00", "2023-02-29", "2024-04-31"] {
+            std::fs::write(&path, format!("age,born\n30,2024-02-29\n25,{}\n", bad_date)).unwrap();
+            let error = import_csv(path.to_str().unwrap(), &mut page, &table).unwrap_err();
+            assert!(error.contains(bad_date), "{}", error);
+            assert_eq!(page.get_tuple(1), None);
+        }
+
+        std::fs::write(&path, "age,born\n30,2024-02-29\n").unwrap();
+        assert_eq!(import_csv(path.to_str().unwrap(), &mut page, &table), Ok(1));
+        assert_eq!(page.get_tuple(1), Some(&vec![DataType::Int32(30), DataType::date(2024, 2, 29)]));
+        std::fs::remove_file(&path).unwrap();
+    }
+
+
+
+    // Test that rows exported by rows_to_csv are imported back unchanged, keeping empty strings apart from NULL
+    #[test]
+    fn test_csv_round_trip() {
+        let mut name = plain_column(3, "name");
+        name.dtype = DataType::Varchar(String::new());
+        let table = plain_table(1, "users", vec![plain_column(2, "age"), name]);
+        let rows = vec![
+            vec![DataType::Int32(30), DataType::Varchar(String::new())],
+            vec![DataType::Null, DataType::Varchar("Smith, Jane".to_string())],
+            vec![DataType::Int32(25), DataType::Null],
+            vec![DataType::Int32(40), DataType::Varchar("Say \"hi\"".to_string())],
+        ];
+        let path = std::env::temp_dir().join("rustgresql_test_round_trip.csv");
+        std::fs::write(&path, format!("age,name\n{}", crate::executor::rows_to_csv(&rows))).unwrap();
+
+        let mut page = Page::empty(DataType::Int32(1), 4096);
+        assert_eq!(import_csv(path.to_str().unwrap(), &mut page, &table), Ok(rows.len()));
+        for (i, row) in rows.iter().enumerate() {
+            assert_eq!(page.get_tuple(i as i32 + 1), Some(row));
+        }
+        std::fs::remove_file(&path).unwrap();
+    }
+
+
+
+    // Test that the JSON export holds the schema and escapes names
+    #[test]
+    fn test_to_json() {
+        let mut catalog = DataCatalog::new("data/catalog.db".to_string());
+        let mut name = plain_column(3, "name");
+        name.dtype = DataType::Varchar(String::new());
+        name.constraints.push(Constraint { oid: DataType::Int32(4), name: DataType::Varchar("name_not_null".to_string()), kind: ConstraintKind::NotNull });
+        catalog.add_table(Table {
+            indexes: vec![Index { oid: DataType::Int32(5), name: DataType::Varchar("age_idx".to_string()), columns: vec![DataType::Varchar("age".to_string())], unique: DataType::Bool(false) }],
+            ..plain_table(1, "my \"users\"", vec![plain_column(2, "age"), name])
+        }).unwrap();
+
+        let json = catalog.to_json();
+        assert!(json.contains("\"name\":\"my \\\"users\\\"\""));
+        assert!(json.contains("{\"name\":\"age\",\"type\":\"INT\",\"constraints\":[]}"));
+        assert!(json.contains("{\"name\":\"name_not_null\",\"kind\":\"NOT NULL\"}"));
+        assert!(json.contains("{\"name\":\"age_idx\",\"columns\":[\"age\"],\"unique\":false}"));
+        assert_eq!(DataCatalog::new("data/catalog.db".to_string()).to_json(), "{\"tables\":[]}");
+    }
+
+
+
+    // Test that tables can be found by their exact name
+    #[test]
+    fn test_get_table() {
+        let mut catalog = two_table_catalog();
+        assert_eq!(catalog.get_table("users").unwrap().oid, DataType::Int32(1));
+        assert_eq!(catalog.get_table("orders").unwrap().oid, DataType::Int32(2));
+        assert!(catalog.get_table("missing").is_none());
+        assert!(catalog.get_table("USERS").is_none());
+
+        catalog.get_table_mut("orders").unwrap().comment = Some("all orders".to_string());
+        assert_eq!(catalog.get_table("orders").unwrap().comment, Some("all orders".to_string()));
+    }
+
+
+
+    // Test that dropping a table removes only that one, also after a round trip
+    #[test]
+    fn test_drop_table() {
+        let catalog = two_table_catalog();
+        let mut catalog = DataCatalog::deserialize(&catalog.serialize(), &mut 0);
+
+        let dropped = catalog.drop_table("users").unwrap();
+        assert_eq!(dropped.oid, DataType::Int32(1));
+        assert!(catalog.drop_table("users").is_none());
+
+        let deserialized = DataCatalog::deserialize(&catalog.serialize(), &mut 0);
+        assert_eq!(deserialized.tables.len(), 1);
+        assert!(deserialized.get_table("orders").is_some());
+        assert!(deserialized.get_table("users").is_none());
+    }
+
+
+
+    // Test that the builder produces the same table as the literal, with oids from the catalog
+    #[test]
+    fn test_table_builder() {
+        let mut catalog = DataCatalog::new("data/catalog.db".to_string());
+        let built = TableBuilder::new()
+            .name("users")
+            .column("id", DataType::Int32(0))
+            .column("email", DataType::Varchar(String::new()))
+            .index("users_email", &["email"], true)
+            .build(&mut catalog);
+
+        let mut email = plain_column(3, "email");
+        email.dtype = DataType::Varchar(String::new());
+        let expected = Table {
+            indexes: vec![Index {
+                oid: DataType::Int32(4),
+                name: DataType::Varchar("users_email".to_string()),
+                columns: vec![DataType::Varchar("email".to_string())],
+                unique: DataType::Bool(true),
+            }],
+            ..plain_table(1, "users", vec![plain_column(2, "id"), email])
+        };
+        assert_eq!(built, expected);
+        assert_eq!(catalog.allocate_oid(), DataType::Int32(5));
+        catalog.add_table(built).unwrap();
+    }
+
+
+
+    // Test that a table can be renamed unless it is missing or the new name is taken
+    #[test]
+    fn test_rename_table() {
+        let mut catalog = two_table_catalog();
+        catalog.rename_table("users", "customers").unwrap();
+        assert!(catalog.get_table("users").is_none());
+        assert_eq!(catalog.get_table("customers").unwrap().oid, DataType::Int32(1));
+
+        assert!(catalog.rename_table("users", "people").is_err());
+        assert!(catalog.rename_table("customers", "orders").is_err());
+        assert_eq!(catalog.get_table("customers").unwrap().oid, DataType::Int32(1));
+        assert_eq!(catalog.get_table("orders").unwrap().oid, DataType::Int32(2));
+    }
+
+
+
+    // Test that streaming a table into a sink produces the same bytes as serialize
+    #[test]
+    fn test_serialize_into() {
+        let table = Table {
+            indexes: vec![Index {
+                oid: DataType::Int32(4),
+                name: DataType::Varchar("users_id".to_string()),
+                columns: vec![DataType::Varchar("id".to_string())],
+                unique: DataType::Bool(true),
+            }],
+            comment: Some("registered users".to_string()),
+            ..plain_table(1, "users", vec![plain_column(2, "id"), plain_column(3, "email")])
+        };
+        let mut streamed = Vec::new();
+        table.serialize_into(&mut streamed).unwrap();
+        assert_eq!(streamed, table.serialize());
+    }
+
+
+
     // This test aims to check that I can serialize and store the data catalog in a file
     // and then deserialize it back to memory
     #[test]
@@ -242,13 +1287,15 @@ mod tests {
             columns: vec![Column {
                 oid: DataType::Int32(2),
                 name: DataType::Varchar("column".to_string()),
+                dtype: DataType::Int32(0),
                 max_value: DataType::Int32(100),
                 min_value: DataType::Int32(0),
                 constraints: vec![Constraint {
                     oid: DataType::Int32(3),
                     name: DataType::Varchar("constraint".to_string()),
-                    dtype: DataType::Varchar("type".to_string()),
+                    kind: ConstraintKind::NotNull,
                 }],
+                comment: None,
             }],
             indexes: vec![Index {
                 oid: DataType::Int32(4),
@@ -256,8 +1303,10 @@ mod tests {
                 columns: vec![DataType::Varchar("column".to_string())],
                 unique: DataType::Bool(true),
             }],
+            primary_key: vec![],
+            comment: None,
         };
-        data_catalog.add_table(table);
+        data_catalog.add_table(table).unwrap();
         let serialized = data_catalog.serialize();
         data_catalog.file.write_all(&serialized).unwrap();
         let mut deserialized = DataCatalog::deserialize(&data_catalog.file.read_to_end().unwrap(), &mut 0);
@@ -1,13 +1,14 @@
 // mod directory;
 // the directory contains the locations of the pages and objects in the database.
-// it is a hashmap of page_id to path and object_id to page_id.
+// it is a hashmap of page_id to path and object_id to the list of pages the object spans.
 // As the catalog, as it is a SmallFile, it can be load into memory and written to disk.
 // The directory should be able to add and remove pages and objects, and get the objects for a page.
 
 use std::collections::HashMap;
+use std::io;
 
 
-use crate::storagemanager::fileops::{ManagedFile, SmallFile};
+use crate::storagemanager::fileops::{check_file_header, file_header, ManagedFile, SmallFile};
 use crate::page::{PageId, PageType, Page};
 use crate::storagemanager::serialization::{DataType, Serializable};
 use crate::catalog::ObjectId;
@@ -15,12 +16,12 @@ use crate::catalog::ObjectId;
 
 struct Directory {
     pages: HashMap<PageId, DataType>,
-    objects: HashMap<ObjectId, PageId>,
+    objects: HashMap<ObjectId, Vec<PageId>>,
     file: ManagedFile,
 }
 
 impl Directory {
-    fn new(pages: Option<HashMap<PageId, DataType>>, objects: Option<HashMap<ObjectId, PageId>>) -> Self {
+    fn new(pages: Option<HashMap<PageId, DataType>>, objects: Option<HashMap<ObjectId, Vec<PageId>>>) -> Self {
         Self {
             pages:pages.unwrap_or(HashMap::new()),
             objects:objects. unwrap_or(HashMap::new()),
@@ -32,21 +33,48 @@ impl Directory {
          self.pages.insert(page_id, path);
    }
 
+   // Registers one more page of the object, in the order the pages are added
    fn add_object(&mut self, object_id: ObjectId, page_id: PageId){
         assert!(self.pages.contains_key(&page_id));
-       self.objects.insert(object_id, page_id);
+       let pages = self.objects.entry(object_id).or_default();
+       if !pages.contains(&page_id) {
+           pages.push(page_id);
+       }
    }
 
+   // Removes the object from all of its pages
    fn remove_object(&mut self, object_id: ObjectId){
        self.objects.remove(&object_id);
    }
 
    fn get_objects_for_page(&self, page_id: PageId) -> Vec<ObjectId>{
-       self.objects.iter().filter(|(_, ref v)| **v == page_id).map(|(k, _)| k.clone()).collect()
+       self.objects.iter().filter(|(_, pages)| pages.contains(&page_id)).map(|(k, _)| k.clone()).collect()
    }
 
-   fn remove_page(&mut self, page_id: PageId){
+   fn get_pages_for_object(&self, object_id: &ObjectId) -> &[PageId]{
+       self.objects.get(object_id).map(Vec::as_slice).unwrap_or(&[])
+   }
+
+   // First page of the object
+   fn get_page_for_object(&self, object_id: &ObjectId) -> Option<PageId>{
+       self.get_pages_for_object(object_id).first().cloned()
+   }
+
+   // Removes the page and every reference to it, so no object points to a missing page.
+   // Objects left without pages are removed too and their ids are returned.
+   fn remove_page(&mut self, page_id: PageId) -> Vec<ObjectId>{
        self.pages.remove(&page_id);
+       for pages in self.objects.values_mut() {
+           pages.retain(|p| *p != page_id);
+       }
+       let orphans: Vec<ObjectId> = self.objects.iter()
+           .filter(|(_, pages)| pages.is_empty())
+           .map(|(k, _)| k.clone())
+           .collect();
+       for object_id in &orphans {
+           self.objects.remove(object_id);
+       }
+       orphans
    }
 
    fn get_page(&self, page_id: PageId) -> Option<&DataType>{
@@ -57,6 +85,21 @@ impl Directory {
        self.file = ManagedFile::new(path);
    }
 
+   // Writes the directory to its file
+   fn save(&self) -> io::Result<()>{
+       self.file.write_all(&self.serialize())
+   }
+
+   // Reads a directory back from the file at path, which becomes its file
+   fn load(path: &str) -> io::Result<Directory>{
+       let file = ManagedFile::new(path);
+       let buffer = file.read_to_end()?;
+       check_file_header(&buffer, &mut 0)?;
+       let mut directory = Directory::deserialize(&buffer, &mut 0);
+       directory.file = file;
+       Ok(directory)
+   }
+
 }
 
 
@@ -64,16 +107,29 @@ impl Serializable for Directory{
 
     fn serialize(&self) -> Vec<u8> {
         // Use serialize_hashmap from DataType
-        let mut serialized = Vec::new();
+        let mut serialized = file_header();
         serialized.extend(DataType::serialize_hashmap(&self.pages));
-        serialized.extend(DataType::serialize_hashmap(&self.objects));
+        // Each object is followed by the list of its pages
+        serialized.extend(DataType::Int32(self.objects.len() as i32).serialize());
+        for (object_id, pages) in &self.objects {
+            serialized.extend(object_id.serialize());
+            serialized.extend(DataType::serialize_list(pages));
+        }
         serialized
        
     }
 
     fn deserialize(buffer: &[u8], offset: &mut usize) -> Self where Self: Sized {
+        if let Err(e) = check_file_header(buffer, offset) {
+            panic!("Cannot read the directory: {}", e);
+        }
         let pages = DataType::deserialize_hashmap(buffer, offset);
-        let objects = DataType::deserialize_hashmap(buffer, offset);
+        let len = DataType::deserialize(buffer, offset).as_int();
+        let mut objects = HashMap::new();
+        for _ in 0..len {
+            let object_id = DataType::deserialize(buffer, offset);
+            objects.insert(object_id, DataType::deserialize_list(buffer, offset));
+        }
         Directory { pages, objects , file: ManagedFile::new("data/directory.db")}
     }
 
@@ -102,7 +158,7 @@ mod tests {
         let data_type = DataType::Int32(42);
         directory.add_page(page_id.clone(), data_type);
         directory.add_object(object_id.clone(), page_id.clone());
-        assert_eq!(directory.objects.get(&object_id), Some(&page_id));
+        assert_eq!(directory.objects.get(&object_id), Some(&vec![page_id]));
     }
 
     #[test]
@@ -142,6 +198,35 @@ mod tests {
         assert!(objects.contains(&object_id2));
     }
 
+    #[test]
+    fn test_get_page_for_object() {
+        let mut directory = Directory::new(None, None);
+        let page_id = PageId::Int32(1);
+        let object_id = ObjectId::Int32(1);
+        directory.add_page(page_id.clone(), DataType::Int32(42));
+        directory.add_object(object_id.clone(), page_id.clone());
+        assert_eq!(directory.get_page_for_object(&object_id), Some(page_id));
+        assert_eq!(directory.get_page_for_object(&ObjectId::Int32(2)), None);
+    }
+
+    #[test]
+    fn test_object_across_pages() {
+        let mut directory = Directory::new(None, None);
+        let object_id = ObjectId::Int32(1);
+        for i in 1..=3 {
+            directory.add_page(PageId::Int32(i), DataType::Int32(42));
+            directory.add_object(object_id.clone(), PageId::Int32(i));
+        }
+        let deserialized = Directory::deserialize(&directory.serialize(), &mut 0);
+        assert_eq!(deserialized.get_pages_for_object(&object_id), &[PageId::Int32(1), PageId::Int32(2), PageId::Int32(3)]);
+        assert_eq!(deserialized.get_objects_for_page(PageId::Int32(2)), vec![object_id.clone()]);
+        assert_eq!(deserialized.get_page_for_object(&object_id), Some(PageId::Int32(1)));
+
+        directory.remove_object(object_id.clone());
+        assert!(directory.get_pages_for_object(&object_id).is_empty());
+        assert!(directory.get_objects_for_page(PageId::Int32(3)).is_empty());
+    }
+
     #[test]
     fn test_remove_page() {
         let mut directory = Directory::new(None, None);
@@ -153,6 +238,26 @@ mod tests {
     
     }
 
+    #[test]
+    fn test_remove_page_cascades() {
+        let mut directory = Directory::new(None, None);
+        directory.add_page(PageId::Int32(1), DataType::Int32(42));
+        directory.add_page(PageId::Int32(2), DataType::Int32(43));
+        directory.add_object(ObjectId::Int32(1), PageId::Int32(1));
+        directory.add_object(ObjectId::Int32(2), PageId::Int32(1));
+        // This one also lives in another page, so it survives
+        directory.add_object(ObjectId::Int32(3), PageId::Int32(1));
+        directory.add_object(ObjectId::Int32(3), PageId::Int32(2));
+
+        let mut removed = directory.remove_page(PageId::Int32(1));
+        removed.sort();
+        assert_eq!(removed, vec![ObjectId::Int32(1), ObjectId::Int32(2)]);
+        assert!(directory.get_objects_for_page(PageId::Int32(1)).is_empty());
+        assert_eq!(directory.objects.len(), 1);
+        assert_eq!(directory.get_pages_for_object(&ObjectId::Int32(3)), &[PageId::Int32(2)]);
+        assert!(directory.objects.values().flatten().all(|page| directory.get_page(page.clone()).is_some()));
+    }
+
     #[test]
     fn test_serialize_deserialize() {
         let mut directory = Directory::new(None, None);
@@ -170,6 +275,31 @@ mod tests {
         assert_eq!(directory.objects, deserialized_directory.objects);
     }
 
+    #[test]
+    fn test_save_load() {
+        let path = std::env::temp_dir().join("rustgresql_test_directory.db");
+        let path = path.to_str().unwrap();
+        let mut directory = Directory::new(None, None);
+        directory.set_file(path);
+        for i in 1..=3 {
+            directory.add_page(PageId::Int32(i), DataType::Varchar(format!("data/page_{}.db", i)));
+            directory.add_object(ObjectId::Int32(10 + i), PageId::Int32(i));
+        }
+        directory.add_object(ObjectId::Int32(20), PageId::Int32(1));
+        directory.save().unwrap();
+
+        let loaded = Directory::load(path).unwrap();
+        assert_eq!(directory.pages, loaded.pages);
+        assert_eq!(directory.objects, loaded.objects);
+        assert_eq!(loaded.file.read_to_end().unwrap(), directory.serialize());
+
+        // A file in the layout used before the format header is rejected instead of misread
+        let header_size = file_header().len();
+        directory.file.write_all(&directory.serialize()[header_size..]).unwrap();
+        assert_eq!(Directory::load(path).err().map(|e| e.kind()), Some(io::ErrorKind::InvalidData));
+        std::fs::remove_file(path).unwrap();
+    }
+
     #[test]
     fn save_to_disk(){
         let mut directory = Directory::new(None, None);
@@ -0,0 +1,356 @@
+// mod executor
+// src/executor.rs
+// This module contains the evaluation helpers used to execute queries.
+// They work on DataType values, so they do not depend on how the query was parsed.
+use std::collections::HashSet;
+
+use crate::storagemanager::serialization::DataType;
+
+
+// Evaluates a scalar SQL function over already evaluated arguments.
+// COALESCE returns its first non null argument.
+// ABS, ROUND, CEIL and FLOOR take a single numeric argument and return NULL for a NULL input.
+fn eval_function(name: &str, args: &[DataType]) -> Result<DataType, String> {
+    let name = name.to_uppercase();
+    if name == "COALESCE" {
+        return Ok(args.iter().find(|arg| **arg != DataType::Null).cloned().unwrap_or(DataType::Null));
+    }
+
+    let arg = match args {
+        [arg] => arg,
+        _ => return Err(format!("{} expects 1 argument, got {}", name, args.len())),
+    };
+    let apply: fn(f64) -> f64 = match name.as_str() {
+        "ABS" => f64::abs,
+        "ROUND" => f64::round,
+        "CEIL" => f64::ceil,
+        "FLOOR" => f64::floor,
+        _ => return Err(format!("Unsupported function: {}", name)),
+    };
+    match arg {
+        DataType::Null => Ok(DataType::Null),
+        // Rounding an integer is a no-op, so only ABS changes its value
+        DataType::Int32(value) if name == "ABS" => value.checked_abs()
+            .map(DataType::Int32)
+            .ok_or(format!("ABS overflow for {}", value)),
+        DataType::Int64(value) if name == "ABS" => value.checked_abs()
+            .map(DataType::Int64)
+            .ok_or(format!("ABS overflow for {}", value)),
+        DataType::Int32(_) | DataType::Int64(_) => Ok(arg.clone()),
+        DataType::Float64(value) => Ok(DataType::Float64(apply(*value))),
+        DataType::Decimal { mantissa, scale } if name == "ABS" => mantissa.checked_abs()
+            .map(|mantissa| DataType::Decimal { mantissa, scale: *scale })
+            .ok_or(format!("ABS overflow for {}", arg)),
+        DataType::Decimal { mantissa, scale } => Ok(round_decimal(&name, *mantissa, *scale)),
+        other => Err(format!("{} expects a numeric argument, got {:?}", name, other)),
+    }
+}
+
+// ROUND, CEIL or FLOOR of a decimal, computed exactly on the mantissa.
+// The result is a whole number, so it has scale 0. ROUND takes halves away from zero, like f64::round.
+fn round_decimal(name: &str, mantissa: i64, scale: u8) -> DataType {
+    let mantissa = mantissa as i128;
+    let (quotient, remainder, half_or_more) = match 10i128.checked_pow(scale as u32) {
+        Some(factor) => (mantissa / factor, mantissa % factor, 2 * (mantissa % factor).abs() >= factor),
+        // The scale is too large for the factor to fit, so the value is well below one half
+        None => (0, mantissa, false),
+    };
+    let rounded = match name {
+        "ROUND" if half_or_more => quotient + remainder.signum(),
+        "CEIL" if remainder > 0 => quotient + 1,
+        "FLOOR" if remainder < 0 => quotient - 1,
+        _ => quotient,
+    };
+    DataType::Decimal { mantissa: rounded as i64, scale: 0 }
+}
+
+
+// Converts the text of a literal token into a value.
+// 'quoted' text is a VARCHAR (with '' standing for a single quote), TRUE, FALSE and NULL are keywords,
+// integers become INT32, or INT64 when they do not fit, and numbers with a point or exponent become FLOAT64.
+fn literal_to_datatype(literal: &str) -> Result<DataType, String> {
+    if literal.len() >= 2 && literal.starts_with('\'') && literal.ends_with('\'') {
+        return Ok(DataType::Varchar(literal[1..literal.len() - 1].replace("''", "'")));
+    }
+    match literal.to_uppercase().as_str() {
+        "TRUE" => return Ok(DataType::Bool(true)),
+        "FALSE" => return Ok(DataType::Bool(false)),
+        "NULL" => return Ok(DataType::Null),
+        _ => {}
+    }
+    if let Ok(value) = literal.parse::<i32>() {
+        return Ok(DataType::Int32(value));
+    }
+    if let Ok(value) = literal.parse::<i64>() {
+        return Ok(DataType::Int64(value));
+    }
+    let is_number = literal.chars().all(|c| c.is_ascii_digit() || matches!(c, '.' | '-' | '+' | 'e' | 'E'));
+    match literal.parse::<f64>() {
+        Ok(value) if is_number => Ok(DataType::Float64(value)),
+        _ => Err(format!("Invalid literal: {}", literal)),
+    }
+}
+
+
+// Evaluates value LIKE pattern.
+// % matches any sequence of characters and _ a single one, and a backslash makes the next character literal.
+// The pattern is anchored at both ends, so 'J%' only matches values starting with J.
+fn like_match(value: &str, pattern: &str) -> bool {
+    // None stands for %, Some(None) for _ and Some(Some(c)) for a literal character
+    let mut tokens: Vec<Option<Option<char>>> = Vec::new();
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        tokens.push(match c {
+            '%' => None,
+            '_' => Some(None),
+            // A trailing backslash stands for itself
+            '\\' => Some(Some(chars.next().unwrap_or('\\'))),
+            c => Some(Some(c)),
+        });
+    }
+
+    // Greedy walk that backtracks to the last % when a character does not match
+    let value: Vec<char> = value.chars().collect();
+    let (mut v, mut p) = (0, 0);
+    let mut backtrack: Option<(usize, usize)> = None;
+    while v < value.len() {
+        match tokens.get(p) {
+            Some(None) => {
+                backtrack = Some((p, v));
+                p += 1;
+            }
+            Some(Some(expected)) if expected.is_none_or(|c| c == value[v]) => {
+                v += 1;
+                p += 1;
+            }
+            _ => match backtrack {
+                // Let the last % swallow one more character
+                Some((star, start)) => {
+                    backtrack = Some((star, start + 1));
+                    p = star + 1;
+                    v = start + 1;
+                }
+                None => return false,
+            },
+        }
+    }
+    tokens[p..].iter().all(|token| token.is_none())
+}
+
+
+// Cartesian product of two inputs, as produced by FROM a CROSS JOIN b.
+// Each row is a left row followed by a right row, and the left rows drive the order.
+fn cross_join(left: &[Vec<DataType>], right: &[Vec<DataType>]) -> Vec<Vec<DataType>> {
+    left.iter()
+        .flat_map(|left_row| right.iter().map(move |right_row| [left_row.as_slice(), right_row.as_slice()].concat()))
+        .collect()
+}
+
+
+// Keeps the first row of each distinct key, as SELECT DISTINCT ON (cols) does.
+// The key is made of the values at the given column indexes, and the rows are expected in ORDER BY order.
+// A key index past the end of a row is an error.
+fn distinct_on(rows: Vec<Vec<DataType>>, key_idx: &[usize]) -> Result<Vec<Vec<DataType>>, String> {
+    for row in &rows {
+        if let Some(i) = key_idx.iter().find(|i| **i >= row.len()) {
+            return Err(format!("DISTINCT ON column {} is out of range for a row of {} columns", i, row.len()));
+        }
+    }
+    let mut seen = HashSet::new();
+    Ok(rows.into_iter()
+        .filter(|row| seen.insert(key_idx.iter().map(|i| row[*i].clone()).collect::<Vec<DataType>>()))
+        .collect())
+}
+
+
+// Applies OFFSET and then LIMIT to a result set.
+// Without a limit every row after the offset is kept, and an offset past the end leaves no rows.
+fn apply_limit_offset(rows: Vec<Vec<DataType>>, limit: Option<usize>, offset: Option<usize>) -> Vec<Vec<DataType>> {
+    rows.into_iter()
+        .skip(offset.unwrap_or(0))
+        .take(limit.unwrap_or(usize::MAX))
+        .collect()
+}
+
+
+// Renders result rows as CSV, one line per row.
+// NULL is an empty field, and fields holding commas, quotes or line breaks are quoted with "" standing for a quote.
+// An empty string is quoted too, so it can be told apart from NULL when the file is imported back.
+pub fn rows_to_csv(rows: &[Vec<DataType>]) -> String {
+    let mut csv = String::new();
+    for row in rows {
+        let fields: Vec<String> = row.iter().map(|value| {
+            let text = match value {
+                DataType::Null => return String::new(),
+                value => value.to_string(),
+            };
+            if text.is_empty() || text.contains([',', '"', '\n', '\r']) {
+                format!("\"{}\"", text.replace('"', "\"\""))
+            } else {
+                text
+            }
+        }).collect();
+        csv.push_str(&fields.join(","));
+        csv.push('\n');
+    }
+    csv
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_coalesce() {
+        assert_eq!(eval_function("COALESCE", &[DataType::Null, DataType::Int32(5)]), Ok(DataType::Int32(5)));
+        assert_eq!(eval_function("coalesce", &[DataType::Null, DataType::Null, DataType::Varchar("default".to_string())]), Ok(DataType::Varchar("default".to_string())));
+        assert_eq!(eval_function("COALESCE", &[DataType::Null]), Ok(DataType::Null));
+    }
+
+    #[test]
+    fn test_numeric_functions() {
+        assert_eq!(eval_function("ABS", &[DataType::Int32(-3)]), Ok(DataType::Int32(3)));
+        assert_eq!(eval_function("ABS", &[DataType::Float64(-2.5)]), Ok(DataType::Float64(2.5)));
+        assert_eq!(eval_function("ROUND", &[DataType::Float64(2.5)]), Ok(DataType::Float64(3.0)));
+        assert_eq!(eval_function("CEIL", &[DataType::Float64(2.1)]), Ok(DataType::Float64(3.0)));
+        assert_eq!(eval_function("FLOOR", &[DataType::Float64(2.9)]), Ok(DataType::Float64(2.0)));
+        assert_eq!(eval_function("FLOOR", &[DataType::Int32(7)]), Ok(DataType::Int32(7)));
+        assert_eq!(eval_function("ABS", &[DataType::Null]), Ok(DataType::Null));
+    }
+
+    #[test]
+    fn test_wide_numeric_functions() {
+        let big = literal_to_datatype("-3000000000").unwrap();
+        assert_eq!(big, DataType::Int64(-3_000_000_000));
+        assert_eq!(eval_function("ABS", &[big]), Ok(DataType::Int64(3_000_000_000)));
+        assert_eq!(eval_function("ROUND", &[DataType::Int64(-3_000_000_000)]), Ok(DataType::Int64(-3_000_000_000)));
+        assert!(eval_function("ABS", &[DataType::Int64(i64::MIN)]).is_err());
+
+        let decimal = |value: &str| DataType::decimal(value).unwrap();
+        assert_eq!(eval_function("ABS", &[decimal("-1.25")]), Ok(decimal("1.25")));
+        assert_eq!(eval_function("ROUND", &[decimal("2.5")]), Ok(decimal("3")));
+        assert_eq!(eval_function("ROUND", &[decimal("-2.5")]), Ok(decimal("-3")));
+        assert_eq!(eval_function("ROUND", &[decimal("2.49")]), Ok(decimal("2")));
+        assert_eq!(eval_function("CEIL", &[decimal("2.1")]), Ok(decimal("3")));
+        assert_eq!(eval_function("CEIL", &[decimal("-2.1")]), Ok(decimal("-2")));
+        assert_eq!(eval_function("FLOOR", &[decimal("-2.1")]), Ok(decimal("-3")));
+        assert_eq!(eval_function("FLOOR", &[decimal("7")]), Ok(decimal("7")));
+        // A scale too large for 10^scale to fit in an i128
+        let tiny = || DataType::Decimal { mantissa: 5, scale: 40 };
+        assert_eq!(eval_function("CEIL", &[tiny()]), Ok(decimal("1")));
+        assert_eq!(eval_function("FLOOR", &[tiny()]), Ok(decimal("0")));
+        assert_eq!(eval_function("ROUND", &[tiny()]), Ok(decimal("0")));
+    }
+
+    #[test]
+    fn test_function_errors() {
+        assert!(eval_function("ABS", &[DataType::Varchar("a".to_string())]).is_err());
+        assert!(eval_function("ABS", &[DataType::Int32(1), DataType::Int32(2)]).is_err());
+        assert!(eval_function("UPPER", &[DataType::Varchar("a".to_string())]).is_err());
+    }
+
+    #[test]
+    fn test_literal_to_datatype() {
+        assert_eq!(literal_to_datatype("30"), Ok(DataType::Int32(30)));
+        assert_eq!(literal_to_datatype("-7"), Ok(DataType::Int32(-7)));
+        assert_eq!(literal_to_datatype("3000000000"), Ok(DataType::Int64(3_000_000_000)));
+        assert_eq!(literal_to_datatype("10.5"), Ok(DataType::Float64(10.5)));
+        assert_eq!(literal_to_datatype("1e3"), Ok(DataType::Float64(1000.0)));
+        assert_eq!(literal_to_datatype("'John'"), Ok(DataType::Varchar("John".to_string())));
+        assert_eq!(literal_to_datatype("'O''Brien'"), Ok(DataType::Varchar("O'Brien".to_string())));
+        assert_eq!(literal_to_datatype("''"), Ok(DataType::Varchar(String::new())));
+        assert_eq!(literal_to_datatype("TRUE"), Ok(DataType::Bool(true)));
+        assert_eq!(literal_to_datatype("false"), Ok(DataType::Bool(false)));
+        assert_eq!(literal_to_datatype("NULL"), Ok(DataType::Null));
+
+        assert!(literal_to_datatype("10.5.3").is_err());
+        assert!(literal_to_datatype("12abc").is_err());
+        assert!(literal_to_datatype("inf").is_err());
+        assert!(literal_to_datatype("'unterminated").is_err());
+    }
+
+    #[test]
+    fn test_like_match() {
+        assert!(like_match("John", "J%"));
+        assert!(like_match("J", "J%"));
+        assert!(!like_match("Mary", "J%"));
+        assert!(!like_match("AJ", "J%"));
+
+        assert!(like_match("Johnson", "%son"));
+        assert!(like_match("sonson", "%son"));
+        assert!(!like_match("Sonya", "%son"));
+
+        assert!(like_match("Jon", "J_n"));
+        assert!(!like_match("Joan", "J_n"));
+        assert!(!like_match("Jn", "J_n"));
+
+        assert!(like_match("100%", "100\\%"));
+        assert!(!like_match("1000", "100\\%"));
+        assert!(like_match("a_b", "a\\_b"));
+        assert!(!like_match("axb", "a\\_b"));
+
+        assert!(like_match("", "%"));
+        assert!(like_match("abc", "a%b%c"));
+        assert!(!like_match("abc", "a%b%d"));
+        assert!(!like_match("", "_"));
+    }
+
+    #[test]
+    fn test_cross_join() {
+        let left = vec![vec![DataType::Int32(1)], vec![DataType::Int32(2)]];
+        let right: Vec<Vec<DataType>> = ["a", "b", "c"].iter().map(|name| vec![DataType::Varchar(name.to_string()), DataType::Bool(true)]).collect();
+        let joined = cross_join(&left, &right);
+        assert_eq!(joined.len(), 6);
+        assert_eq!(joined[0], vec![DataType::Int32(1), DataType::Varchar("a".to_string()), DataType::Bool(true)]);
+        assert_eq!(joined[5], vec![DataType::Int32(2), DataType::Varchar("c".to_string()), DataType::Bool(true)]);
+        assert!(cross_join(&left, &[]).is_empty());
+    }
+
+    #[test]
+    fn test_distinct_on() {
+        // Ordered by a, b
+        let rows = vec![
+            vec![DataType::Int32(1), DataType::Int32(10)],
+            vec![DataType::Int32(1), DataType::Int32(20)],
+            vec![DataType::Int32(2), DataType::Int32(5)],
+            vec![DataType::Null, DataType::Int32(1)],
+            vec![DataType::Null, DataType::Int32(2)],
+        ];
+        assert_eq!(distinct_on(rows.clone(), &[0]), Ok(vec![
+            vec![DataType::Int32(1), DataType::Int32(10)],
+            vec![DataType::Int32(2), DataType::Int32(5)],
+            vec![DataType::Null, DataType::Int32(1)],
+        ]));
+        assert_eq!(distinct_on(rows.clone(), &[0, 1]), Ok(rows.clone()));
+        assert_eq!(distinct_on(rows.clone(), &[]).map(|rows| rows.len()), Ok(1));
+        assert_eq!(distinct_on(rows, &[0, 2]), Err("DISTINCT ON column 2 is out of range for a row of 2 columns".to_string()));
+        assert_eq!(distinct_on(vec![], &[5]), Ok(vec![]));
+    }
+
+    #[test]
+    fn test_apply_limit_offset() {
+        let rows: Vec<Vec<DataType>> = (1..=5).map(|i| vec![DataType::Int32(i)]).collect();
+        let ids = |rows: Vec<Vec<DataType>>| rows.iter().map(|row| row[0].as_int()).collect::<Vec<i32>>();
+
+        assert_eq!(ids(apply_limit_offset(rows.clone(), Some(2), None)), vec![1, 2]);
+        assert_eq!(ids(apply_limit_offset(rows.clone(), None, Some(3))), vec![4, 5]);
+        assert_eq!(ids(apply_limit_offset(rows.clone(), Some(2), Some(1))), vec![2, 3]);
+        assert_eq!(ids(apply_limit_offset(rows.clone(), Some(10), Some(3))), vec![4, 5]);
+        assert!(apply_limit_offset(rows.clone(), Some(2), Some(5)).is_empty());
+        assert!(apply_limit_offset(rows.clone(), None, Some(8)).is_empty());
+        assert_eq!(apply_limit_offset(rows.clone(), None, None), rows);
+        assert!(apply_limit_offset(rows, Some(0), None).is_empty());
+    }
+
+    #[test]
+    fn test_rows_to_csv() {
+        let rows = vec![
+            vec![DataType::Int32(1), DataType::Varchar("Smith, Jane".to_string()), DataType::Null],
+            vec![DataType::Int32(2), DataType::Varchar("Say \"hi\"".to_string()), DataType::Bool(true)],
+        ];
+        assert_eq!(rows_to_csv(&rows), "1,\"Smith, Jane\",\n2,\"Say \"\"hi\"\"\",true\n");
+        assert_eq!(rows_to_csv(&[vec![DataType::Varchar(String::new()), DataType::Null]]), "\"\",\n");
+        assert_eq!(rows_to_csv(&[]), "");
+    }
+}
@@ -1,4 +1,7 @@
 mod storagemanager;
 mod catalog;
 mod page;
-mod directory;
\ No newline at end of file
+mod directory;
+mod executor;
+mod bufferpool;
+mod wal;
\ No newline at end of file
@@ -4,10 +4,14 @@
 // A page is a unit of storage in the database.
 // A page has a header, a list of slots and a list of tuples.
 use std::collections::VecDeque;
+use std::io::{self, Write};
 use crate::storagemanager::serialization::{Serializable, DataType};
+use crate::storagemanager::btree::{BTree, Entry};
 
 
-const MAX_PAGE_SIZE: u16 = 4096; // SELECTED MAX PAGE SIZE
+const DEFAULT_PAGE_SIZE: u16 = 4096; // PAGE SIZE USED WHEN NONE IS GIVEN
+const INDEX_DEGREE: i32 = 16; // MINIMUM DEGREE OF THE B-TREES BUILT BY build_index
+const FREE_SLOT_SIZE: i32 = 5; // BYTES TAKEN BY EACH ID OF THE FREE LIST (INT32)
 
 
 // CUSTOM TYPES
@@ -19,24 +23,42 @@ type TupleId = DataType;
 // The header of the page contains metadata about the page
 #[derive(Debug)]
 struct Header{
+    checksum: DataType, // CRC32 OF THE REST OF THE PAGE, COMPUTED WHEN THE PAGE IS SERIALIZED
     page_type: PageType,
     free_space: DataType, // AMOUNT OF FREE SPACE IN THE PAGE 
     page_number: PageId, // PAGE NUMBER
     next_page: PageId,
     last_slot: TupleId, // POINTER TO THE TUPLE ID OF THE LAST SLOT
     offset: DataType, // OFFSET WHERE THE LAST TUPLE STARTS
+    page_size: DataType, // SIZE OF THE PAGE IN BYTES
 }
 
 impl Header{
     fn new(page_type: PageType, page_number: PageId, next_page: PageId, free_space: Option<DataType>) -> Self{
-        Header{
+        Header::with_page_size(page_type, page_number, next_page, free_space, DEFAULT_PAGE_SIZE as i32)
+    }
+
+    fn with_page_size(page_type: PageType, page_number: PageId, next_page: PageId, free_space: Option<DataType>, page_size: i32) -> Self{
+        let mut header = Header{
+            checksum: DataType::Int32(0),
             page_type,
-            free_space: free_space.unwrap_or(DataType::Int32(MAX_PAGE_SIZE as i32)),
+            free_space: DataType::Int32(0),
             page_number,
             next_page,
             last_slot: DataType::Int32(0), // INITIALLY NO SLOTS
-            offset: DataType::Int32(MAX_PAGE_SIZE as i32 - 1), // INITIALLY NO OFFSET
-        }
+            offset: DataType::Int32(page_size - 1), // INITIALLY NO OFFSET
+            page_size: DataType::Int32(page_size),
+        };
+        let capacity = header.capacity();
+        assert!(capacity >= 0, "Page size {} is too small: the header and the slot and free lists take {} bytes", page_size, page_size - capacity);
+        header.free_space = free_space.unwrap_or(DataType::Int32(capacity));
+        header
+    }
+
+    // Space available for slots, free slot ids and tuples in an empty page
+    // The header, the lengths of the slot array and the free list and the last byte of the page are not usable
+    fn capacity(&self) -> i32{
+        self.page_size.as_int() - self.serialize().len() as i32 - 5 - 5 - 1
     }
 
     fn set_free_space(&mut self, free_space: DataType){
@@ -47,19 +69,31 @@ impl Header{
 impl Serializable for Header {
     fn serialize(&self) -> Vec<u8>{
         let mut serialized = Vec::new();
+        serialized.extend(self.checksum.serialize());
+        serialized.extend(self.page_size.serialize());
         serialized.extend(self.page_type.serialize());
         serialized.extend(self.free_space.serialize());
         serialized.extend(self.page_number.serialize());
         serialized.extend(self.next_page.serialize());
+        serialized.extend(self.last_slot.serialize());
+        serialized.extend(self.offset.serialize());
         serialized
     }
 
     fn deserialize(serialized: &[u8], offset: &mut usize) -> Self{
+        let checksum = DataType::deserialize(serialized, offset);
+        let page_size = DataType::deserialize(serialized, offset);
         let page_type = PageType::deserialize(serialized, offset);
         let free_space = DataType::deserialize(serialized, offset);
         let page_number = DataType::deserialize(serialized, offset);
         let next_page = DataType::deserialize(serialized, offset);
-        Header::new(page_type,  page_number, next_page, Some(free_space))
+        let last_slot = DataType::deserialize(serialized, offset);
+        let tuple_offset = DataType::deserialize(serialized, offset);
+        let mut header = Header::with_page_size(page_type,  page_number, next_page, Some(free_space), page_size.as_int());
+        header.checksum = checksum;
+        header.last_slot = last_slot;
+        header.offset = tuple_offset;
+        header
     }
 }
 
@@ -93,6 +127,9 @@ struct Slot{
     tuple_id: TupleId, // TUPLE ID
     offset: DataType, // OFFSET WHERE THE TUPLE STARTS
     length: DataType, // LENGTH OF THE TUPLE
+    deleted: bool, // TOMBSTONE: THE TUPLE WAS REMOVED BUT THE SLOT IS KEPT
+    chunk: bool, // THE TUPLE HOLDS PART OF THE SERIALIZED BYTES OF A LARGE TUPLE, SEE write_large_tuple
+    overflow: PageId, // PAGE HOLDING THE CONTINUATION OF THE TUPLE, NULL IF THE TUPLE IS COMPLETE
 
 }
 
@@ -102,6 +139,9 @@ impl Slot{
             tuple_id,
             offset,
             length,
+            deleted: false,
+            chunk: false,
+            overflow: DataType::Null,
         }
     }
 }
@@ -112,6 +152,9 @@ impl Serializable for Slot {
         serialized.extend(self.tuple_id.serialize());
         serialized.extend(self.offset.serialize());
         serialized.extend(self.length.serialize());
+        serialized.extend(DataType::Bool(self.deleted).serialize());
+        serialized.extend(DataType::Bool(self.chunk).serialize());
+        serialized.extend(self.overflow.serialize());
         serialized
     }
 
@@ -119,7 +162,14 @@ impl Serializable for Slot {
         let tuple_id = DataType::deserialize(serialized, offset);
         let tuple_offset = DataType::deserialize(serialized, offset);
         let length = DataType::deserialize(serialized, offset);
-        Slot::new(tuple_id, tuple_offset, length)
+        let deleted = DataType::deserialize(serialized, offset).as_bool();
+        let chunk = DataType::deserialize(serialized, offset).as_bool();
+        let overflow = DataType::deserialize(serialized, offset);
+        let mut slot = Slot::new(tuple_id, tuple_offset, length);
+        slot.deleted = deleted;
+        slot.chunk = chunk;
+        slot.overflow = overflow;
+        slot
     }
 }
 
@@ -129,6 +179,7 @@ impl Serializable for Slot {
 struct Tuple{
     tuple_id: TupleId,
     data: Vec<DataType>,
+    chunk: Option<Vec<u8>>, // RAW BYTES OF A TUPLE SPLIT ACROSS PAGES, THE DATA IS EMPTY WHEN SET
 }
 
 impl Tuple{
@@ -136,20 +187,80 @@ impl Tuple{
         Tuple{
             tuple_id,
             data,
+            chunk: None,
+        }
+    }
+
+    // Piece of the serialized values of a tuple too large for a single page
+    fn chunk(tuple_id: TupleId, bytes: Vec<u8>) -> Self{
+        Tuple{
+            tuple_id,
+            data: Vec::new(),
+            chunk: Some(bytes),
         }
     }
+
+    // Compact encoding for rows with many nulls:
+    // tuple id, number of columns, a null bitmap (one bit per column) and then only the non-null values
+    fn serialize_with_bitmap(&self) -> Vec<u8>{
+        let mut serialized = Vec::new();
+        serialized.extend(self.tuple_id.serialize());
+        serialized.extend(DataType::Int32(self.data.len() as i32).serialize());
+        let mut bitmap = vec![0u8; self.data.len().div_ceil(8)];
+        for (i, value) in self.data.iter().enumerate() {
+            if *value == DataType::Null {
+                bitmap[i / 8] |= 1 << (i % 8);
+            }
+        }
+        serialized.extend(bitmap);
+        for value in self.data.iter().filter(|value| **value != DataType::Null) {
+            serialized.extend(value.serialize());
+        }
+        serialized
+    }
+
+    fn deserialize_with_bitmap(serialized: &[u8], offset: &mut usize) -> Self{
+        let tuple_id = DataType::deserialize(serialized, offset);
+        let columns = DataType::deserialize(serialized, offset).as_int() as usize;
+        let bitmap_size = columns.div_ceil(8);
+        let bitmap = serialized[*offset..*offset + bitmap_size].to_vec();
+        *offset += bitmap_size;
+        let data = (0..columns).map(|i| {
+            if bitmap[i / 8] & (1 << (i % 8)) != 0 {
+                DataType::Null
+            } else {
+                DataType::deserialize(serialized, offset)
+            }
+        }).collect();
+        Tuple::new(tuple_id, data)
+    }
 }
 
 impl Serializable for Tuple {
     fn serialize(&self) -> Vec<u8>{
         let mut serialized = Vec::new();
         serialized.extend(self.tuple_id.serialize());
-        serialized.extend(DataType::serialize_list(&self.data));
+        match &self.chunk {
+            // A chunk has a NULL where the value list would start, followed by its length and its bytes
+            Some(bytes) => {
+                serialized.extend(DataType::Null.serialize());
+                serialized.extend(DataType::Int32(bytes.len() as i32).serialize());
+                serialized.extend(bytes);
+            }
+            None => serialized.extend(DataType::serialize_list(&self.data)),
+        }
         serialized
     }
 
     fn deserialize(serialized: &[u8], offset: &mut usize) -> Self{
         let tuple_id = DataType::deserialize(serialized, offset);
+        if serialized.get(*offset) == Some(&DataType::Null.get_type()) {
+            DataType::deserialize(serialized, offset);
+            let length = DataType::deserialize(serialized, offset).as_int() as usize;
+            let bytes = serialized[*offset..*offset + length].to_vec();
+            *offset += length;
+            return Tuple::chunk(tuple_id, bytes);
+        }
         let data = DataType::deserialize_list(serialized, offset);
         Tuple::new(tuple_id, data)
     }
@@ -160,18 +271,19 @@ impl Serializable for Tuple {
 // STRUCT PAGE
 // A page is a unit of storage in the database
 // A page has a header, a list of slots and a list of tuples
-// MAX PAGE SIZE is 4096 bytes
+// The page size is stored in the header and defaults to 4096 bytes
 // The page is serialized as follows:
 // 1. Serialize the header  
 // 2. Serialize the slots
 // 3. Serialize the tuples
-// The slots are stored at the beginning of the page and grow towards the end
+// The slots are stored at the beginning of the page and grow towards the end, followed by the free list
 // The tuples are stored at the end of the page and grow towards the beginning
 #[derive(Debug)]
 pub struct Page{
     header: Header,
     slots: VecDeque<Slot>,    
     data: VecDeque<Tuple>,
+    free_slots: Vec<i32>, // IDS OF THE TOMBSTONED SLOTS, SORTED SO THE SMALLEST IS REUSED FIRST
     
 
 }
@@ -185,9 +297,16 @@ impl Page{
             header,
             slots: slots.unwrap_or_default(),
             data: data.unwrap_or_default(),
+            free_slots: Vec::new(),
         }
     }
 
+    // Empty data page, for callers outside this module that cannot build a header
+    pub fn empty(page_number: PageId, page_size: i32) -> Page{
+        let header = Header::with_page_size(PageType::Data(DataType::Varchar("DATA".to_string())), page_number, DataType::Int32(0), None, page_size);
+        Page::new(header, None, None)
+    }
+
     fn reduce_free_space(&mut self, reduce_by: i32){
         assert!(reduce_by <= self.get_free_space(), "Not enough free space: Free space: {}, Reduce by: {}", self.get_free_space(), reduce_by);
         let free_space = self.get_free_space() - reduce_by;
@@ -198,20 +317,31 @@ impl Page{
         self.header.free_space.as_int()
     }
 
-    fn append_tuple(&mut self, tuple_data: Vec<DataType>){
+    fn page_size(&self) -> i32{
+        self.header.page_size.as_int()
+    }
+
+    pub fn append_tuple(&mut self, tuple_data: Vec<DataType>) -> i32{
+        self.push_tuple(Tuple::new(DataType::Null, tuple_data))
+    }
+
+    fn push_tuple(&mut self, mut tuple: Tuple) -> i32{
         // Logic to append a tuple to the page
-        // 1. Create a new tuple with the tuple_id as the last_slot + 1
+        // 1. Give the tuple the smallest freed id, or the last_slot + 1 if no slot was freed
         // 2. Serialize the tuple and get the size
         // 3. Update the offset of the tuple
 
         println!("Initial offset {:?}", self.header.offset.as_int());
+        if !self.free_slots.is_empty() {
+            return self.reuse_slot(tuple);
+        }
         let tuple_id = self.header.last_slot.as_int() + 1;
-        let tuple = Tuple::new(DataType::Int32(tuple_id), tuple_data);
+        tuple.tuple_id = DataType::Int32(tuple_id);
         let mut tuple_size = tuple.serialize().len() as i32;
         let mut offset = self.header.offset.as_int() - tuple_size;
     
 
-        if self.header.last_slot.as_int() == 0 {
+        if self.data.is_empty() {
             // First tuple
             offset -= 5; // Reservar 5 bytes adicionales.
             tuple_size += 5;
@@ -220,7 +350,8 @@ impl Page{
         println!("Tuple size {:?}", tuple_size);
 
         // Create a new slot
-        let slot = Slot::new(DataType::Int32(tuple_id), DataType::Int32(offset), DataType::Int32(tuple_size));
+        let mut slot = Slot::new(DataType::Int32(tuple_id), DataType::Int32(offset), DataType::Int32(tuple_size));
+        slot.chunk = tuple.chunk.is_some();
         let slot_size = slot.serialize().len() as i32;
         // Update the header
         self.header.last_slot = DataType::Int32(tuple_id);
@@ -230,8 +361,548 @@ impl Page{
         self.slots.push_back(slot);
         self.reduce_free_space(tuple_size);
         self.data.push_front(tuple);
-        
+        tuple_id
+    }
+
+    // Stores a tuple under the smallest freed id, reviving its tombstoned slot
+    // The slot array does not grow, and the entry of the id in the free list is given back to the free space
+    fn reuse_slot(&mut self, mut tuple: Tuple) -> i32{
+        let tuple_id = self.free_slots.remove(0);
+        tuple.tuple_id = DataType::Int32(tuple_id);
+        let mut tuple_size = tuple.serialize().len() as i32;
+        if self.data.is_empty() {
+            tuple_size += 5;
+        }
+        let offset = self.header.offset.as_int() - tuple_size;
+        let index = self.slots.iter().position(|slot| slot.tuple_id.as_int() == tuple_id).unwrap();
+        let overflow_size = self.slots[index].overflow.serialize().len() as i32 - DataType::Null.serialize().len() as i32;
+        self.slots[index] = Slot::new(DataType::Int32(tuple_id), DataType::Int32(offset), DataType::Int32(tuple_size));
+        self.slots[index].chunk = tuple.chunk.is_some();
+        self.header.offset = DataType::Int32(offset);
+        self.increase_free_space(FREE_SLOT_SIZE + overflow_size);
+        self.reduce_free_space(tuple_size);
+        self.data.push_front(tuple);
+        tuple_id
+    }
+
+    fn increase_free_space(&mut self, increase_by: i32){
+        let free_space = self.get_free_space() + increase_by;
+        self.header.set_free_space(DataType::Int32(free_space));
+    }
+
+    // Bytes needed to append a tuple with the given data
+    // It needs room for the tuple, its slot and, for the first tuple, the length of the tuple list
+    // A reused slot is already in place, and its entry in the free list is released
+    fn space_needed(&self, tuple_data: &[DataType]) -> i32{
+        self.space_for(&Tuple::new(DataType::Int32(self.header.last_slot.as_int() + 1), tuple_data.to_vec()))
     }
+
+    fn space_for(&self, tuple: &Tuple) -> i32{
+        let mut needed = tuple.serialize().len() as i32;
+        if self.free_slots.is_empty() {
+            needed += Slot::new(tuple.tuple_id.clone(), DataType::Int32(0), DataType::Int32(0)).serialize().len() as i32;
+        } else {
+            needed -= FREE_SLOT_SIZE;
+        }
+        if self.data.is_empty() {
+            needed += 5;
+        }
+        needed
+    }
+
+    // Checks whether a tuple with the given data can be appended to the page
+    pub fn can_fit(&self, tuple_data: &[DataType]) -> bool{
+        self.space_needed(tuple_data) <= self.get_free_space()
+    }
+
+    // Checks whether all the tuples can be appended one after the other.
+    // The first ones take the freed slots, and the rest need a new slot each.
+    pub fn can_fit_all(&self, tuples: &[Vec<DataType>]) -> bool{
+        let slot_size = Slot::new(DataType::Int32(0), DataType::Int32(0), DataType::Int32(0)).serialize().len() as i32;
+        let mut needed = 0;
+        for (i, tuple_data) in tuples.iter().enumerate() {
+            needed += Tuple::new(DataType::Int32(0), tuple_data.clone()).serialize().len() as i32;
+            needed += if i < self.free_slots.len() { -FREE_SLOT_SIZE } else { slot_size };
+        }
+        if self.data.is_empty() && !tuples.is_empty() {
+            needed += 5;
+        }
+        needed <= self.get_free_space()
+    }
+
+    // Number of bytes of a large tuple that fit in the page as a single chunk, keeping `reserve` bytes free
+    fn chunk_room(&self, reserve: i32) -> usize{
+        let empty_chunk = Tuple::chunk(DataType::Int32(self.header.last_slot.as_int() + 1), Vec::new());
+        (self.get_free_space() - reserve - self.space_for(&empty_chunk)).max(0) as usize
+    }
+
+    // Appends a piece of the serialized bytes of a large tuple, marking its slot as a chunk
+    fn append_chunk(&mut self, bytes: &[u8]) -> i32{
+        self.push_tuple(Tuple::chunk(DataType::Null, bytes.to_vec()))
+    }
+
+    // Points the slot of a tuple to the page holding its continuation
+    // The page id takes more bytes than the Null it replaces, so the difference is taken from the free space
+    fn set_overflow(&mut self, tuple_id: i32, page_id: PageId){
+        let index = self.live_slot_index(tuple_id).unwrap();
+        let growth = page_id.serialize().len() as i32 - self.slots[index].overflow.serialize().len() as i32;
+        self.reduce_free_space(growth);
+        self.slots[index].overflow = page_id;
+    }
+
+    // Position of the live slot of a tuple in the slot array
+    fn live_slot_index(&self, tuple_id: i32) -> Option<usize>{
+        self.slots.iter().position(|slot| slot.tuple_id.as_int() == tuple_id && !slot.deleted)
+    }
+
+    // Logic to remove a tuple from the page
+    // 1. Mark the slot of the tuple as deleted and remove the tuple
+    //    The slot is kept so the ids of the other tuples and the slot array layout do not change
+    // 2. The tuples are serialized as a contiguous block that starts at the header offset,
+    //    so the block shrinks by the size of the removed tuple and the newer tuples move towards the end
+    // 3. Give the bytes of the tuple back to the free space
+    // 4. Add the id to the free list, so the next appended tuple takes the slot
+    fn remove_tuple(&mut self, tuple_id: i32) -> bool{
+        let slot_index = match self.live_slot_index(tuple_id){
+            Some(index) => index,
+            None => return false,
+        };
+        let removed_offset = self.slots[slot_index].offset.as_int();
+        self.slots[slot_index].deleted = true;
+        self.slots[slot_index].length = DataType::Int32(0);
+        let tuple_index = self.data.iter().position(|tuple| tuple.tuple_id.as_int() == tuple_id).unwrap();
+        let tuple = self.data.remove(tuple_index).unwrap();
+
+        let mut tuple_size = tuple.serialize().len() as i32;
+        if self.data.is_empty() {
+            // The 5 bytes reserved for the tuple list length are released with the last tuple
+            tuple_size += 5;
+        }
+
+        // Tuples appended after the removed one are stored before it, so they are shifted
+        for other in self.slots.iter_mut().filter(|other| !other.deleted){
+            if other.offset.as_int() < removed_offset {
+                other.offset = DataType::Int32(other.offset.as_int() + tuple_size);
+            }
+        }
+        self.header.offset = DataType::Int32(self.header.offset.as_int() + tuple_size);
+
+        self.increase_free_space(tuple_size);
+        let position = self.free_slots.partition_point(|id| *id < tuple_id);
+        self.free_slots.insert(position, tuple_id);
+        self.reduce_free_space(FREE_SLOT_SIZE);
+        true
+    }
+
+    // Logic to update a tuple of the page
+    // 1. If the new data is not bigger than the old one, the tuple is overwritten in place
+    //    and the bytes it no longer needs are given back to the free space
+    // 2. Otherwise the tuple is relocated to the beginning of the tuple block,
+    //    which requires enough free space for the extra bytes
+    // In both cases the tuple block stays contiguous and the slot offsets are updated accordingly
+    fn update_tuple(&mut self, tuple_id: i32, new_data: Vec<DataType>) -> Result<(), String>{
+        let slot_index = self.live_slot_index(tuple_id)
+            .ok_or(format!("Tuple {} not found", tuple_id))?;
+        if self.slots[slot_index].chunk {
+            return Err(format!("Tuple {} is stored across pages and cannot be updated in place", tuple_id));
+        }
+        let tuple_index = self.data.iter().position(|tuple| tuple.tuple_id.as_int() == tuple_id).unwrap();
+
+        let new_tuple = Tuple::new(DataType::Int32(tuple_id), new_data);
+        let old_size = self.data[tuple_index].serialize().len() as i32;
+        let new_size = new_tuple.serialize().len() as i32;
+        let slot_offset = self.slots[slot_index].offset.as_int();
+        // The first tuple of the page also accounts for the tuple list length
+        let reserved = self.slots[slot_index].length.as_int() - old_size;
+
+        if new_size <= old_size {
+            // In place: the tuple and the newer ones, stored before it, move towards the end
+            let shrink_by = old_size - new_size;
+            for slot in self.slots.iter_mut().filter(|slot| !slot.deleted){
+                if slot.offset.as_int() <= slot_offset {
+                    slot.offset = DataType::Int32(slot.offset.as_int() + shrink_by);
+                }
+            }
+            self.slots[slot_index].length = DataType::Int32(new_size + reserved);
+            self.header.offset = DataType::Int32(self.header.offset.as_int() + shrink_by);
+            self.data[tuple_index] = new_tuple;
+            self.increase_free_space(shrink_by);
+            return Ok(());
+        }
+
+        let grow_by = new_size - old_size;
+        if grow_by > self.get_free_space() {
+            return Err(format!("Not enough free space to relocate tuple {}: Free space: {}, Needed: {}", tuple_id, self.get_free_space(), grow_by));
+        }
+
+        // Relocate: take the tuple out of the block, then place it at the beginning of the block
+        self.data.remove(tuple_index);
+        for slot in self.slots.iter_mut().filter(|slot| !slot.deleted){
+            if slot.offset.as_int() < slot_offset {
+                slot.offset = DataType::Int32(slot.offset.as_int() + old_size);
+            }
+        }
+        let offset = self.header.offset.as_int() + old_size - new_size;
+        self.slots[slot_index].offset = DataType::Int32(offset);
+        self.slots[slot_index].length = DataType::Int32(new_size + reserved);
+        self.header.offset = DataType::Int32(offset);
+        self.data.push_front(new_tuple);
+        self.reduce_free_space(grow_by);
+        Ok(())
+    }
+
+    // Rewrites the live tuples contiguously at the end of the page, in slot order.
+    // The live slots get their offsets recomputed as if the tuples had just been appended,
+    // and the header offset and free space are derived again from the live tuples.
+    // Tombstoned slots are kept, so they still take space in the slot array and the free list.
+    fn compact(&mut self){
+        let mut tuples: Vec<Tuple> = self.data.drain(..).collect();
+        let mut offset = self.page_size() - 1;
+        let mut used = self.free_slots.len() as i32 * FREE_SLOT_SIZE;
+        for slot in self.slots.iter_mut(){
+            used += slot.serialize().len() as i32;
+            if slot.deleted {
+                continue;
+            }
+            let tuple_index = tuples.iter().position(|tuple| tuple.tuple_id == slot.tuple_id).unwrap();
+            let tuple = tuples.swap_remove(tuple_index);
+            let mut tuple_size = tuple.serialize().len() as i32;
+            if self.data.is_empty() {
+                // The first tuple reserves the bytes of the tuple list length
+                tuple_size += 5;
+            }
+            offset -= tuple_size;
+            slot.offset = DataType::Int32(offset);
+            slot.length = DataType::Int32(tuple_size);
+            used += tuple_size;
+            // Newer tuples are stored first
+            self.data.push_front(tuple);
+        }
+        self.header.offset = DataType::Int32(offset);
+        self.header.set_free_space(DataType::Int32(self.header.capacity() - used));
+    }
+
+    // Derives the free space from what the page holds instead of the running counter:
+    // the capacity minus the slots, the free list, the live tuples and, if there are tuples, the length of the tuple list
+    fn recompute_free_space(&self) -> i32{
+        let slots: i32 = self.slots.iter().map(|slot| slot.serialize().len() as i32).sum();
+        let tuples: i32 = self.data.iter().map(|tuple| tuple.serialize().len() as i32).sum();
+        let tuple_list = if self.data.is_empty() { 0 } else { 5 };
+        self.header.capacity() - slots - self.free_slots.len() as i32 * FREE_SLOT_SIZE - tuples - tuple_list
+    }
+
+    // Checks that the free space in the header has not drifted from the contents of the page
+    fn verify_free_space(&self) -> bool{
+        self.recompute_free_space() == self.get_free_space()
+    }
+
+    // Finds the slot of a tuple without scanning the whole slot array.
+    // Slots are appended in tuple id order, so when ids are dense the slot can be indexed directly.
+    // Otherwise, we fall back to a binary search over the sorted slots.
+    fn slot_for(&self, tuple_id: i32) -> Option<&Slot> {
+        let first_id = self.slots.front()?.tuple_id.as_int();
+        if tuple_id >= first_id {
+            if let Some(slot) = self.slots.get((tuple_id - first_id) as usize) {
+                if slot.tuple_id.as_int() == tuple_id {
+                    return Some(slot);
+                }
+            }
+        }
+        self.slots
+            .binary_search_by_key(&tuple_id, |slot| slot.tuple_id.as_int())
+            .ok()
+            .map(|index| &self.slots[index])
+    }
+
+    // Returns the data of the tuple with the given id, if the page holds it and it was not removed
+    // Tuples stored across pages are read with read_large_tuple instead
+    pub fn get_tuple(&self, tuple_id: i32) -> Option<&Vec<DataType>>{
+        let slot = self.slot_for(tuple_id)?;
+        if slot.deleted || slot.chunk {
+            return None;
+        }
+        self.data.iter().find(|tuple| tuple.tuple_id.as_int() == tuple_id).map(|tuple| &tuple.data)
+    }
+
+    // Iterates over the live tuples of the page in slot order, skipping tombstoned slots and chunks of large tuples
+    fn tuples(&self) -> impl Iterator<Item = &Tuple>{
+        self.slots.iter()
+            .filter(|slot| !slot.deleted && !slot.chunk)
+            .filter_map(move |slot| self.data.iter().find(|tuple| tuple.tuple_id == slot.tuple_id))
+    }
+
+    // Returns the index-th live tuple in slot order, tombstoned slots do not count
+    fn tuple_at(&self, index: usize) -> Option<&Tuple>{
+        self.tuples().nth(index)
+    }
+
+    // Walks the tuples in slot order and yields only the ones whose data satisfies the predicate.
+    // Filtering happens during the walk, so non matching tuples are never collected.
+    fn scan_filtered<'a, P>(&'a self, pred: P) -> impl Iterator<Item = &'a Tuple> + 'a
+    where
+        P: Fn(&[DataType]) -> bool + 'a,
+    {
+        self.tuples().filter(move |tuple| pred(&tuple.data))
+    }
+
+    // Bytes held by a live chunk of a large tuple
+    fn chunk_bytes(&self, tuple_id: i32) -> Option<&[u8]>{
+        if self.slot_for(tuple_id)?.deleted {
+            return None;
+        }
+        self.data.iter().find(|tuple| tuple.tuple_id.as_int() == tuple_id)?.chunk.as_deref()
+    }
+
+    // Live tuples whose value at the given column equals value, in slot order.
+    // A column out of range matches nothing.
+    fn find_tuples_by_column(&self, column: usize, value: &DataType) -> Vec<&Tuple>{
+        self.tuples().filter(|tuple| tuple.data.get(column) == Some(value)).collect()
+    }
+}
+
+
+// Returns the (column index, new value) pairs where the new tuple differs from the old one
+// Columns that only exist in the new tuple are reported as changed
+fn tuple_diff(old: &Tuple, new: &Tuple) -> Vec<(usize, DataType)> {
+    new.data.iter()
+        .enumerate()
+        .filter(|(i, value)| old.data.get(*i) != Some(*value))
+        .map(|(i, value)| (i, value.clone()))
+        .collect()
+}
+
+
+// Stores a tuple that may not fit in a single page
+// A tuple that fits is appended as usual. Otherwise its serialized values are split in chunks:
+// the first chunk goes to the given page and the rest to new overflow pages, each in a slot marked as a chunk.
+// The slot of each chunk points to the page of the next one, and the overflow pages are also linked through next_page.
+// Overflow pages are numbered from `first_overflow_page` and returned so the caller can persist them.
+// Nothing is appended when the given page has no room for a chunk.
+fn write_large_tuple(page: &mut Page, tuple_data: Vec<DataType>, first_overflow_page: i32) -> Result<(i32, Vec<Page>), String> {
+    if page.can_fit(&tuple_data) {
+        return Ok((page.append_tuple(tuple_data), Vec::new()));
+    }
+
+    // Every chunk but the last one needs room for the pointer that replaces the Null overflow in its slot
+    let pointer_size = DataType::Int32(0).serialize().len() as i32 - DataType::Null.serialize().len() as i32;
+    let bytes = DataType::serialize_list(&tuple_data);
+    let page_size = page.page_size();
+    let first_room = page.chunk_room(pointer_size).min(bytes.len());
+    if first_room == 0 {
+        return Err(format!("Not enough free space in page {} for a tuple of {} bytes", page.header.page_number, bytes.len()));
+    }
+    if Page::empty(DataType::Int32(first_overflow_page), page_size).chunk_room(pointer_size) == 0 {
+        return Err(format!("Overflow pages of {} bytes cannot hold a tuple of {} bytes", page_size, bytes.len()));
+    }
+
+    let tuple_id = page.append_chunk(&bytes[..first_room]);
+    let mut stored = first_room;
+    let mut overflow_pages: Vec<Page> = Vec::new();
+    let mut last_chunk = tuple_id;
+    while stored < bytes.len() {
+        let page_number = first_overflow_page + overflow_pages.len() as i32;
+        let mut overflow_page = Page::empty(DataType::Int32(page_number), page_size);
+        let room = overflow_page.chunk_room(pointer_size).min(bytes.len() - stored);
+        let chunk_id = overflow_page.append_chunk(&bytes[stored..stored + room]);
+
+        // Link the previous chunk to this page
+        match overflow_pages.last_mut() {
+            Some(previous) => {
+                previous.set_overflow(last_chunk, DataType::Int32(page_number));
+                previous.header.next_page = DataType::Int32(page_number);
+            }
+            None => page.set_overflow(tuple_id, DataType::Int32(page_number)),
+        }
+        overflow_pages.push(overflow_page);
+        last_chunk = chunk_id;
+        stored += room;
+    }
+    Ok((tuple_id, overflow_pages))
+}
+
+
+// Reads back a tuple written by write_large_tuple, following its overflow pages with the given loader
+// The chunks are concatenated and the values are deserialized from the reassembled bytes
+fn read_large_tuple<F>(page: &Page, tuple_id: i32, mut load: F) -> Option<Vec<DataType>>
+where
+    F: FnMut(PageId) -> Option<Page>,
+{
+    let slot = page.slot_for(tuple_id)?;
+    if !slot.chunk {
+        return page.get_tuple(tuple_id).cloned();
+    }
+    let mut bytes = page.chunk_bytes(tuple_id)?.to_vec();
+    let mut next = slot.overflow.clone();
+    while next != DataType::Null {
+        let overflow_page = load(next)?;
+        let slot = overflow_page.slots.iter().find(|slot| !slot.deleted && slot.chunk)?;
+        bytes.extend(overflow_page.chunk_bytes(slot.tuple_id.as_int())?);
+        next = slot.overflow.clone();
+    }
+    Some(DataType::deserialize_list(&bytes, &mut 0))
+}
+
+
+// Walks a list of pages linked through next_page, starting at start.
+// The list ends at a page whose next_page is Int32(0) or when the loader cannot find a page.
+fn iter_chain<F>(start: PageId, mut load: F) -> impl Iterator<Item = Page>
+where
+    F: FnMut(PageId) -> Option<Page>,
+{
+    let mut next = Some(start);
+    std::iter::from_fn(move || {
+        let page = load(next.take()?)?;
+        if page.header.next_page != DataType::Int32(0) {
+            next = Some(page.header.next_page.clone());
+        }
+        Some(page)
+    })
+}
+
+
+// Loads an index page into an in-memory B-Tree
+// Each tuple of an index page is a (key, tuple_id) pair
+fn btree_from_index_page(page: &Page, degree: i32) -> BTree<i32, TupleId> {
+    assert!(matches!(page.header.page_type, PageType::Index(_)), "Not an index page: {:?}", page.header.page_type);
+    let entries = page.data.iter()
+        .map(|tuple| Entry::new(tuple.data[0].as_int(), tuple.data[1].clone()))
+        .collect();
+    BTree::bulk_load(entries, degree)
+}
+
+
+// Builds an index over a column of a data page, mapping the column value to the tuple id.
+// Only INT32 values can be keys, so tuples with a NULL or non integer value in the column are not indexed.
+fn build_index(page: &Page, column: usize) -> BTree<i32, i32> {
+    let entries = page.tuples()
+        .filter_map(|tuple| match tuple.data.get(column) {
+            Some(DataType::Int32(key)) => Some(Entry::new(*key, tuple.tuple_id.as_int())),
+            _ => None,
+        })
+        .collect();
+    BTree::bulk_load(entries, INDEX_DEGREE)
+}
+
+
+// Size of the checksum at the beginning of a serialized page (type marker + 4 bytes)
+// The page size comes right after it, so both can be read without parsing the rest of the header
+const CHECKSUM_SIZE: usize = 5;
+
+// CRC32 (IEEE polynomial, bitwise implementation)
+fn crc32(bytes: &[u8]) -> u32 {
+    !crc32_update(0xFFFFFFFF, bytes)
+}
+
+// Feeds more bytes into a CRC32 that is being computed
+fn crc32_update(mut crc: u32, bytes: &[u8]) -> u32 {
+    for byte in bytes {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xEDB88320 & mask);
+        }
+    }
+    crc
+}
+
+// Sink that only keeps the CRC32 of the bytes written to it
+struct Crc32Writer {
+    crc: u32,
+}
+
+impl Crc32Writer {
+    fn new() -> Self {
+        Crc32Writer { crc: 0xFFFFFFFF }
+    }
+
+    fn finish(&self) -> u32 {
+        !self.crc
+    }
+}
+
+impl Write for Crc32Writer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.crc = crc32_update(self.crc, buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+// Checks the checksum of a serialized page without deserializing it
+// The checksum covers every byte of the page after the checksum itself
+pub fn verify_checksum(serialized: &[u8]) -> bool {
+    let read_int = |position: usize| -> Option<i32> {
+        let bytes = serialized.get(position..position + CHECKSUM_SIZE)?;
+        if bytes[0] != DataType::Int32(0).get_type() {
+            return None;
+        }
+        Some(DataType::deserialize(bytes, &mut 0).as_int())
+    };
+    let (checksum, page_size) = match (read_int(0), read_int(CHECKSUM_SIZE)) {
+        (Some(checksum), Some(page_size)) => (checksum, page_size as usize),
+        _ => return false,
+    };
+    if page_size < 2 * CHECKSUM_SIZE || serialized.len() < page_size {
+        return false;
+    }
+    checksum as u32 == crc32(&serialized[CHECKSUM_SIZE..page_size])
+}
+
+
+impl Page {
+    // Writes every byte of the page after the checksum, without building the page in memory
+    // 1. The rest of the header
+    // 2. The slot array followed by the free list
+    // 3. Zeros up to the header offset, where the tuple block starts
+    // 4. The tuple block and zeros up to the end of the page
+    fn write_body<W: Write>(&self, w: &mut W) -> io::Result<()>{
+        let page_size = self.page_size() as usize;
+        let int_size = DataType::Int32(0).serialize().len();
+        let header = self.header.serialize();
+        w.write_all(&header[CHECKSUM_SIZE..])?;
+
+        DataType::Int32(self.slots.len() as i32).serialize_into(w)?;
+        let mut written = header.len() + int_size;
+        for slot in &self.slots {
+            let serialized_slot = slot.serialize();
+            w.write_all(&serialized_slot)?;
+            written += serialized_slot.len();
+        }
+        // The free list goes right after the slot array
+        let free_slots: Vec<DataType> = self.free_slots.iter().map(|id| DataType::Int32(*id)).collect();
+        DataType::serialize_list_into(&free_slots, w)?;
+        written += (free_slots.len() + 1) * int_size;
+
+        // AN EMPTY PAGE HAS NO TUPLE BLOCK TO PLACE
+        if !self.data.is_empty() {
+            let tuple_offset = self.header.offset.as_int() as usize;
+            let tuples_size = int_size + self.data.iter().map(|tuple| tuple.serialize().len()).sum::<usize>();
+            assert!(tuples_size <= page_size - written, "Not enough space: Tuples size: {}, Free space: {}", tuples_size, page_size - written);
+            write_zeros(w, tuple_offset - written)?;
+            DataType::Int32(self.data.len() as i32).serialize_into(w)?;
+            for tuple in &self.data {
+                tuple.serialize_into(w)?;
+            }
+            written = tuple_offset + tuples_size;
+        }
+        write_zeros(w, page_size - written)?;
+        Ok(())
+    }
+}
+
+
+// Writes count zero bytes, a small block at a time
+fn write_zeros<W: Write>(w: &mut W, mut count: usize) -> io::Result<()> {
+    let zeros = [0u8; 64];
+    while count > 0 {
+        let size = count.min(zeros.len());
+        w.write_all(&zeros[..size])?;
+        count -= size;
+    }
+    Ok(())
 }
 
 
@@ -242,50 +913,41 @@ impl Page{
 // THE SLOTS ARE STORED AT THE BEGINNING OF THE PAGE AND GROW TOWARDS THE END
 impl Serializable for Page {
     fn serialize(&self) -> Vec<u8>{
-        // ALLOCATE MAX_PAGE SIZE
-        let mut serialized = vec![0; MAX_PAGE_SIZE as usize];
-        // Fill the first bytes with the header
-        let serialized_header = self.header.serialize();
-        let slot_offset = serialized_header.len();
-        serialized.splice(0..slot_offset, serialized_header.iter().cloned());
-        // Fill the next bytes with the slot array
-        
-        let serialized_slots = Slot::serialize_vecdeque(&self.slots);
-        let slots_size = serialized_slots.len();
-        println!("Serialized slots: {:?}", serialized_slots);
-        serialized.splice(slot_offset..slot_offset + slots_size, serialized_slots.iter().cloned());
-
-        // FILL THE END OF THE PAGE WITH THE TUPLES
-        let tuples = self.data.clone();
-        // Serialize the tuples
-        let serialized_tuples = Tuple::serialize_vecdeque(&tuples);
-        println!("Serialized tuples: {:?}", serialized_tuples);
-
-        // Place the tuples at the end of the page
-        let tuple_offset = self.header.offset.as_int() as usize;
-        let tuples_size = serialized_tuples.len();
-        println!("Tuple offset: {:?}", tuple_offset);
-        println!("Tuples size: {:?}", tuples_size);
-
-        assert!(tuples_size <= MAX_PAGE_SIZE as usize - slot_offset - slots_size , "Not enough space: Tuples size: {}, Free space: {}", tuples_size, MAX_PAGE_SIZE as usize - slot_offset- slots_size);
-
-        serialized.splice(tuple_offset..tuple_offset + tuples_size, serialized_tuples.iter().cloned());
-
-        // Return the serialized page
+        let mut serialized = Vec::with_capacity(self.page_size() as usize);
+        // Writing to a Vec cannot fail
+        self.serialize_into(&mut serialized).unwrap();
         serialized
     }
 
+    // The checksum comes first but covers the rest of the page, so the page is written twice:
+    // once into a writer that only computes the checksum, and then into w after the checksum
+    fn serialize_into<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        let mut checksum = Crc32Writer::new();
+        self.write_body(&mut checksum)?;
+        DataType::Int32(checksum.finish() as i32).serialize_into(w)?;
+        self.write_body(w)
+    }
+
     fn deserialize(serialized: &[u8], offset: &mut usize) -> Self{
+        assert!(verify_checksum(&serialized[*offset..]), "page checksum mismatch");
         let header = Header::deserialize(serialized, offset);
         println!("Deserialized header: {:?}", header);
         // Deserialize the slots
         let slots = Slot::deserialize_vecdeque(serialized, offset);
         println!("Deserialized slots: {:?}", slots);
+        let free_slots: Vec<i32> = DataType::deserialize_list(serialized, offset).iter().map(|id| id.as_int()).collect();
+
+        // A page without live slots has no tuples to read
+        if slots.iter().all(|slot| slot.deleted) {
+            let mut page = Page::new(header, Some(slots), None);
+            page.free_slots = free_slots;
+            return page;
+        }
 
         // The tuples are stored at the end of the page
-        // Get the last tuple offset
+        // The tuple block always starts at the header offset
 
-        let mut last_tuple_offset = slots.back().unwrap().offset.as_int() as usize;
+        let mut last_tuple_offset = header.offset.as_int() as usize;
        
 
     
@@ -295,7 +957,9 @@ impl Serializable for Page {
         let tuples = Tuple::deserialize_vecdeque(serialized, &mut last_tuple_offset);
         
        
-        Page::new(header, Some(slots), Some(tuples))
+        let mut page = Page::new(header, Some(slots), Some(tuples));
+        page.free_slots = free_slots;
+        page
     }
 }
 
@@ -316,6 +980,8 @@ mod tests {
         assert_eq!(header.free_space, deserialized.free_space);
         assert_eq!(header.page_number, deserialized.page_number);
         assert_eq!(header.next_page, deserialized.next_page);
+        assert_eq!(header.last_slot, deserialized.last_slot);
+        assert_eq!(header.offset, deserialized.offset);
     }
 
     #[test]
@@ -327,6 +993,8 @@ mod tests {
         assert_eq!(slot.tuple_id, deserialized.tuple_id);
         assert_eq!(slot.offset, deserialized.offset);
         assert_eq!(slot.length, deserialized.length);
+        assert_eq!(slot.deleted, deserialized.deleted);
+        assert_eq!(slot.overflow, deserialized.overflow);
     }
 
     #[test]
@@ -339,6 +1007,28 @@ mod tests {
         assert_eq!(tuple.data, deserialized.data);
     }
 
+    #[test]
+    fn test_tuple_bitmap_serialization() {
+        let data: Vec<DataType> = (0..16).map(|i| if i % 2 == 0 { DataType::Null } else { DataType::Int32(i) }).collect();
+        let tuple = Tuple::new(DataType::Int32(1), data);
+        let serialized = tuple.serialize_with_bitmap();
+        let mut offset = 0;
+        let deserialized = Tuple::deserialize_with_bitmap(&serialized, &mut offset);
+        assert_eq!(offset, serialized.len());
+        assert_eq!(tuple.data, deserialized.data);
+
+        // 8 nulls take 16 bytes in the naive encoding and 2 bitmap bytes here
+        assert_eq!(tuple.serialize().len() - serialized.len(), 16 - 2);
+    }
+
+    #[test]
+    fn test_tuple_diff() {
+        let old = Tuple::new(DataType::Int32(1), vec![DataType::Int32(10), DataType::Varchar("test".to_string()), DataType::Bool(true)]);
+        let new = Tuple::new(DataType::Int32(1), vec![DataType::Int32(10), DataType::Varchar("changed".to_string()), DataType::Bool(true)]);
+        assert_eq!(tuple_diff(&old, &new), vec![(1, DataType::Varchar("changed".to_string()))]);
+        assert!(tuple_diff(&old, &old).is_empty());
+    }
+
     #[test]
     fn test_page_serialization() {
         
@@ -360,6 +1050,35 @@ mod tests {
         assert_eq!(page.data[0].data, deserialized.data[0].data);
     }
 
+    #[test]
+    fn test_empty_page_serialization() {
+        let page = page_with(vec![]);
+        let serialized = page.serialize();
+        assert_eq!(serialized.len(), DEFAULT_PAGE_SIZE as usize);
+        let mut offset = 0;
+        let deserialized = Page::deserialize(&serialized, &mut offset);
+
+        assert_eq!(page.header.free_space, deserialized.header.free_space);
+        assert_eq!(page.header.page_number, deserialized.header.page_number);
+        assert_eq!(page.header.offset, deserialized.header.offset);
+        assert_eq!(page.header.last_slot, deserialized.header.last_slot);
+        assert!(deserialized.slots.is_empty());
+        assert!(deserialized.data.is_empty());
+    }
+
+    #[test]
+    fn test_append_after_round_trip() {
+        let page = page_with(vec![vec![DataType::Int32(10)], vec![DataType::Int32(20)]]);
+        let mut deserialized = Page::deserialize(&page.serialize(), &mut 0);
+        assert_eq!(page.header.offset, deserialized.header.offset);
+        assert_eq!(page.header.last_slot, deserialized.header.last_slot);
+
+        // New tuples keep getting fresh ids and are placed before the existing ones
+        deserialized.append_tuple(vec![DataType::Int32(30)]);
+        assert_eq!(deserialized.slots.back().unwrap().tuple_id, DataType::Int32(3));
+        assert_round_trip(&deserialized);
+    }
+
     #[test]
     fn test_append_tuple() {
         let header = Header::new(PageType::Data(DataType::Varchar("DATA".to_string())), DataType::Int32(0), DataType::Int32(1), None);
@@ -371,6 +1090,568 @@ mod tests {
         assert_eq!(page.data[0].data, tuple_data);
     }
 
+    #[test]
+    fn test_scan_filtered() {
+        let mut page = page_with(vec![]);
+        page.append_tuple(vec![DataType::Varchar("john".to_string()), DataType::Int32(25)]);
+        page.append_tuple(vec![DataType::Varchar("mary".to_string()), DataType::Int32(42)]);
+        page.append_tuple(vec![DataType::Varchar("paul".to_string()), DataType::Int32(31)]);
+        page.append_tuple(vec![DataType::Varchar("anna".to_string()), DataType::Int32(30)]);
+
+        // age > 30
+        let matches: Vec<&Tuple> = page.scan_filtered(|data| data[1].as_int() > 30).collect();
+        assert_eq!(matches.len(), 2);
+        assert_eq!(matches[0].data[0], DataType::Varchar("mary".to_string()));
+        assert_eq!(matches[1].data[0], DataType::Varchar("paul".to_string()));
+    }
+
+    #[test]
+    fn test_find_tuples_by_column() {
+        let mut page = page_with(vec![]);
+        page.append_tuple(vec![DataType::Varchar("john".to_string()), DataType::Varchar("madrid".to_string())]);
+        page.append_tuple(vec![DataType::Varchar("mary".to_string()), DataType::Varchar("paris".to_string())]);
+        page.append_tuple(vec![DataType::Varchar("paul".to_string()), DataType::Varchar("madrid".to_string())]);
+        let removed = page.append_tuple(vec![DataType::Varchar("anna".to_string()), DataType::Varchar("madrid".to_string())]);
+        page.remove_tuple(removed);
+
+        let madrid = DataType::Varchar("madrid".to_string());
+        let matches = page.find_tuples_by_column(1, &madrid);
+        assert_eq!(matches.len(), 2);
+        assert_eq!(matches[0].data[0], DataType::Varchar("john".to_string()));
+        assert_eq!(matches[1].data[0], DataType::Varchar("paul".to_string()));
+
+        assert!(page.find_tuples_by_column(1, &DataType::Varchar("rome".to_string())).is_empty());
+        assert!(page.find_tuples_by_column(5, &madrid).is_empty());
+    }
+
+    #[test]
+    fn test_iter_chain() {
+        let data_page = |page_number: i32, next_page: i32| {
+            let mut page = Page::empty(DataType::Int32(page_number), DEFAULT_PAGE_SIZE as i32);
+            page.header.next_page = DataType::Int32(next_page);
+            page
+        };
+        let mut pages = std::collections::HashMap::new();
+        pages.insert(DataType::Int32(1), data_page(1, 2));
+        pages.insert(DataType::Int32(2), data_page(2, 0));
+        pages.insert(DataType::Int32(3), data_page(3, 0));
+
+        let chain: Vec<Page> = iter_chain(DataType::Int32(1), |page_id| pages.remove(&page_id)).collect();
+        let numbers: Vec<DataType> = chain.iter().map(|page| page.header.page_number.clone()).collect();
+        assert_eq!(numbers, vec![DataType::Int32(1), DataType::Int32(2)]);
+
+        // A missing page ends the walk
+        assert_eq!(iter_chain(DataType::Int32(9), |_| None).count(), 0);
+    }
+
+    #[test]
+    fn test_slot_for() {
+        let mut page = page_with(vec![]);
+        for i in 0..10 {
+            page.append_tuple(vec![DataType::Int32(i)]);
+        }
+        for tuple_id in 1..=10 {
+            let linear = page.slots.iter().find(|slot| slot.tuple_id.as_int() == tuple_id).unwrap();
+            let slot = page.slot_for(tuple_id).unwrap();
+            assert_eq!(slot.tuple_id, linear.tuple_id);
+            assert_eq!(slot.offset, linear.offset);
+        }
+        assert!(page.slot_for(0).is_none());
+        assert!(page.slot_for(11).is_none());
+
+        // Sparse ids fall back to the binary search
+        page.slots.remove(3);
+        assert_eq!(page.slot_for(7).unwrap().tuple_id, DataType::Int32(7));
+        assert!(page.slot_for(4).is_none());
+    }
+
+    #[test]
+    fn test_remove_tuple() {
+        let mut page = page_with(vec![]);
+        let initial_free_space = page.get_free_space();
+        page.append_tuple(vec![DataType::Int32(10), DataType::Varchar("test".to_string())]);
+        let free_space_after_one = page.get_free_space();
+        page.append_tuple(vec![DataType::Int32(20), DataType::Varchar("test2".to_string())]);
+        page.append_tuple(vec![DataType::Int32(30), DataType::Varchar("test3".to_string())]);
+
+        assert!(page.remove_tuple(2));
+        assert!(!page.remove_tuple(2));
+        assert_eq!(page.slots.len(), 3);
+        assert_eq!(page.data.len(), 2);
+
+        let serialized = page.serialize();
+        let deserialized = Page::deserialize(&serialized, &mut 0);
+        assert_eq!(page.header.free_space, deserialized.header.free_space);
+        assert_eq!(deserialized.data.len(), 2);
+        assert_eq!(deserialized.data[0].data, vec![DataType::Int32(30), DataType::Varchar("test3".to_string())]);
+        assert_eq!(deserialized.data[1].data, vec![DataType::Int32(10), DataType::Varchar("test".to_string())]);
+
+        // Removing the newest and then the last tuple gives all the tuple space back
+        // The tombstoned slots still take their place in the slot array, and their ids in the free list
+        let slot_size = page.slots[0].serialize().len() as i32 + FREE_SLOT_SIZE;
+        assert!(page.remove_tuple(3));
+        assert_eq!(page.get_free_space(), free_space_after_one - 2 * slot_size);
+        let deserialized = Page::deserialize(&page.serialize(), &mut 0);
+        assert_eq!(deserialized.data.len(), 1);
+        assert_eq!(deserialized.data[0].data, vec![DataType::Int32(10), DataType::Varchar("test".to_string())]);
+        assert!(page.remove_tuple(1));
+        assert_eq!(page.get_free_space(), initial_free_space - 3 * slot_size);
+        let deserialized = Page::deserialize(&page.serialize(), &mut 0);
+        assert!(deserialized.data.is_empty());
+    }
+
+    #[test]
+    fn test_remove_tuple_keeps_tombstone() {
+        let mut page = page_with(vec![vec![DataType::Int32(10)], vec![DataType::Int32(20)], vec![DataType::Int32(30)]]);
+        assert!(page.remove_tuple(2));
+        assert_eq!(page.get_tuple(2), None);
+        assert!(page.update_tuple(2, vec![DataType::Int32(21)]).is_err());
+
+        let deserialized = Page::deserialize(&page.serialize(), &mut 0);
+        assert_eq!(deserialized.slots.len(), 3);
+        assert!(deserialized.slots[1].deleted);
+        assert_eq!(deserialized.get_tuple(2), None);
+        assert_eq!(deserialized.get_tuple(3), Some(&vec![DataType::Int32(30)]));
+    }
+
+    #[test]
+    fn test_reuse_freed_slot() {
+        let mut page = page_with(vec![vec![DataType::Int32(10)], vec![DataType::Int32(20)], vec![DataType::Int32(30)], vec![DataType::Int32(40)]]);
+        page.remove_tuple(3);
+        page.remove_tuple(2);
+        let free_space = page.get_free_space();
+
+        // The free list survives a round trip
+        let mut page = Page::deserialize(&page.serialize(), &mut 0);
+        assert_eq!(page.free_slots, vec![2, 3]);
+        assert!(page.can_fit(&[DataType::Int32(50)]));
+
+        // The smallest freed id is reused first and the slot array does not grow
+        assert_eq!(page.append_tuple(vec![DataType::Int32(50)]), 2);
+        assert_eq!(page.append_tuple(vec![DataType::Int32(60)]), 3);
+        assert_eq!(page.append_tuple(vec![DataType::Int32(70)]), 5);
+        assert_eq!(page.slots.len(), 5);
+        assert!(page.free_slots.is_empty());
+        assert_eq!(page.get_tuple(2), Some(&vec![DataType::Int32(50)]));
+        assert_eq!(page.get_tuple(3), Some(&vec![DataType::Int32(60)]));
+        assert!(page.get_free_space() < free_space);
+        assert_round_trip(&page);
+    }
+
+    #[test]
+    fn test_tuples_iterator() {
+        let mut page = page_with(vec![vec![DataType::Int32(10)], vec![DataType::Int32(20)], vec![DataType::Int32(30)]]);
+        page.remove_tuple(2);
+        let values: Vec<&Vec<DataType>> = page.tuples().map(|tuple| &tuple.data).collect();
+        assert_eq!(values, vec![&vec![DataType::Int32(10)], &vec![DataType::Int32(30)]]);
+
+        // Relocated tuples keep their slot order, and the appended tuple takes the freed slot
+        page.update_tuple(1, vec![DataType::Int32(10), DataType::Int32(11)]).unwrap();
+        page.append_tuple(vec![DataType::Int32(40)]);
+        let ids: Vec<i32> = page.tuples().map(|tuple| tuple.tuple_id.as_int()).collect();
+        assert_eq!(ids, vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn test_tuple_at() {
+        let mut page = page_with(vec![vec![DataType::Int32(10)], vec![DataType::Int32(20)], vec![DataType::Int32(30)], vec![DataType::Int32(40)]]);
+        assert_eq!(page.tuple_at(1).unwrap().data, vec![DataType::Int32(20)]);
+        page.remove_tuple(2);
+        assert_eq!(page.tuple_at(0).unwrap().data, vec![DataType::Int32(10)]);
+        assert_eq!(page.tuple_at(1).unwrap().data, vec![DataType::Int32(30)]);
+        assert_eq!(page.tuple_at(2).unwrap().data, vec![DataType::Int32(40)]);
+        assert!(page.tuple_at(3).is_none());
+    }
+
+    #[test]
+    fn test_can_fit() {
+        let mut page = page_with(vec![]);
+        let tuple = vec![DataType::Int32(10), DataType::Varchar("test".to_string()), DataType::Float64(1.5)];
+        let mut appended = 0;
+        while page.can_fit(&tuple) {
+            page.append_tuple(tuple.clone());
+            appended += 1;
+        }
+        assert!(appended > 0);
+        assert!(page.get_free_space() >= 0);
+        assert!(!page.can_fit(&tuple));
+
+        // The full page can still be serialized and read back
+        assert_round_trip(&page);
+    }
+
+    #[test]
+    fn test_can_fit_all() {
+        let mut page = Page::empty(DataType::Int32(0), 256);
+        let tuple = vec![DataType::Int32(10), DataType::Varchar("test".to_string())];
+        let mut fitting = Vec::new();
+        while page.can_fit_all(&[fitting.clone(), vec![tuple.clone()]].concat()) {
+            fitting.push(tuple.clone());
+        }
+        assert!(!fitting.is_empty());
+        assert!(page.can_fit_all(&[]));
+
+        // Exactly the tuples that can_fit accepts one by one
+        for tuple_data in &fitting {
+            assert!(page.can_fit(tuple_data));
+            page.append_tuple(tuple_data.clone());
+        }
+        assert!(!page.can_fit(&tuple));
+
+        // Freed slots are reused, so the removed tuples fit again
+        page.remove_tuple(1);
+        page.remove_tuple(2);
+        assert!(page.can_fit_all(&[tuple.clone(), tuple.clone()]));
+        assert!(!page.can_fit_all(&[tuple.clone(), tuple.clone(), tuple]));
+    }
+
+    #[test]
+    fn test_small_page_size() {
+        let mut page = Page::empty(DataType::Int32(0), 256);
+        assert!(page.get_free_space() < 256);
+        let tuple = vec![DataType::Int32(10), DataType::Varchar("test".to_string())];
+        let mut appended = 0;
+        while page.can_fit(&tuple) {
+            page.append_tuple(tuple.clone());
+            appended += 1;
+        }
+        // Each tuple takes 19 bytes plus its slot, so only a few fit in 256 bytes
+        assert!(appended > 0 && appended < 8);
+
+        let serialized = page.serialize();
+        assert_eq!(serialized.len(), 256);
+        let deserialized = Page::deserialize(&serialized, &mut 0);
+        assert_eq!(deserialized.page_size(), 256);
+        assert_eq!(deserialized.tuples().count(), appended);
+    }
+
+    #[test]
+    #[should_panic(expected = "Page size 16 is too small")]
+    fn test_page_size_too_small() {
+        Page::empty(DataType::Int32(0), 16);
+    }
+
+    #[test]
+    fn test_smallest_page_size() {
+        // A page that only fits the header and the empty lists can still be stored
+        let capacity = Page::empty(DataType::Int32(0), DEFAULT_PAGE_SIZE as i32).header.capacity();
+        let smallest = DEFAULT_PAGE_SIZE as i32 - capacity;
+        let page = Page::empty(DataType::Int32(0), smallest);
+        assert_eq!(page.get_free_space(), 0);
+        assert_eq!(page.serialize().len(), smallest as usize);
+        assert_eq!(Page::deserialize(&page.serialize(), &mut 0).page_size(), smallest);
+        assert!(!page.can_fit(&[DataType::Null]));
+    }
+
+    #[test]
+    fn test_large_tuple() {
+        let mut page = Page::empty(DataType::Int32(1), 256);
+        page.append_tuple(vec![DataType::Int32(1)]);
+        let large: Vec<DataType> = (0..200).map(DataType::Int32).collect();
+
+        let (tuple_id, overflow_pages) = write_large_tuple(&mut page, large.clone(), 2).unwrap();
+        assert!(overflow_pages.len() > 2);
+        assert!(page.get_free_space() >= 0);
+        // Overflow pages are chained through next_page
+        for pair in overflow_pages.windows(2) {
+            assert_eq!(pair[0].header.next_page, pair[1].header.page_number);
+        }
+
+        // Every page goes through serialization, as if it was stored on disk
+        let stored: Vec<Vec<u8>> = overflow_pages.iter().map(|page| page.serialize()).collect();
+        let page = Page::deserialize(&page.serialize(), &mut 0);
+        let read = read_large_tuple(&page, tuple_id, |page_id| {
+            stored.get(page_id.as_int() as usize - 2).map(|bytes| Page::deserialize(bytes, &mut 0))
+        });
+        assert_eq!(read, Some(large));
+
+        // A tuple that fits is stored without overflow pages
+        let mut page = page_with(vec![]);
+        let (tuple_id, overflow_pages) = write_large_tuple(&mut page, vec![DataType::Int32(1)], 2).unwrap();
+        assert!(overflow_pages.is_empty());
+        assert_eq!(read_large_tuple(&page, tuple_id, |_| None), Some(vec![DataType::Int32(1)]));
+    }
+
+    #[test]
+    fn test_large_value() {
+        // A single value bigger than the page is split across pages by its bytes
+        let mut page = page_with(vec![vec![DataType::Int32(1)]]);
+        let large = vec![DataType::Int32(7), DataType::Varchar("x".repeat(10 * 1024))];
+        let (tuple_id, overflow_pages) = write_large_tuple(&mut page, large.clone(), 2).unwrap();
+        assert_eq!(overflow_pages.len(), 2);
+        assert!(page.slot_for(tuple_id).unwrap().chunk);
+        assert!(overflow_pages.iter().all(|overflow_page| overflow_page.slots[0].chunk));
+        assert!(page.verify_free_space());
+
+        // Chunks are not rows, so scans only see the regular tuples
+        assert_eq!(page.tuples().count(), 1);
+        assert_eq!(page.get_tuple(tuple_id), None);
+        assert!(page.update_tuple(tuple_id, vec![]).is_err());
+
+        let stored: Vec<Vec<u8>> = overflow_pages.iter().map(|page| page.serialize()).collect();
+        let page = Page::deserialize(&page.serialize(), &mut 0);
+        let read = read_large_tuple(&page, tuple_id, |page_id| {
+            stored.get(page_id.as_int() as usize - 2).map(|bytes| Page::deserialize(bytes, &mut 0))
+        });
+        assert_eq!(read, Some(large));
+    }
+
+    #[test]
+    fn test_large_tuple_without_room() {
+        let mut page = page_with(vec![]);
+        page.header.set_free_space(DataType::Int32(100));
+        while page.can_fit(&[DataType::Int32(1)]) {
+            page.append_tuple(vec![DataType::Int32(1)]);
+        }
+        let free_space = page.get_free_space();
+        let slots = page.slots.len();
+
+        let error = write_large_tuple(&mut page, vec![DataType::Varchar("x".repeat(10 * 1024))], 2).unwrap_err();
+        // Only the size of the tuple is reported, not its value
+        assert!(error.contains("10250 bytes"), "{}", error);
+        assert!(!error.contains("xxx"));
+        assert_eq!(page.get_free_space(), free_space);
+        assert_eq!(page.slots.len(), slots);
+    }
+
+    #[test]
+    fn test_checksum() {
+        let page = page_with(vec![vec![DataType::Int32(10), DataType::Varchar("test".to_string())]]);
+        let serialized = page.serialize();
+        assert!(verify_checksum(&serialized));
+
+        for position in [0, 3, 7, 20, 2000, serialized.len() - 20] {
+            let mut corrupted = serialized.clone();
+            corrupted[position] ^= 0x01;
+            assert!(!verify_checksum(&corrupted));
+        }
+    }
+
+    #[test]
+    fn test_page_serialize_into() {
+        // Records the size of the largest single write
+        struct LargestWrite {
+            bytes: Vec<u8>,
+            largest: usize,
+        }
+        impl Write for LargestWrite {
+            fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+                self.largest = self.largest.max(buf.len());
+                self.bytes.extend_from_slice(buf);
+                Ok(buf.len())
+            }
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let mut page = page_with((1..=20).map(|i| vec![DataType::Int32(i), DataType::Varchar(format!("test{}", i))]).collect());
+        page.remove_tuple(4);
+        for page in [page, page_with(vec![])] {
+            let mut streamed = LargestWrite { bytes: Vec::new(), largest: 0 };
+            page.serialize_into(&mut streamed).unwrap();
+            assert_eq!(streamed.bytes, page.serialize());
+            assert!(verify_checksum(&streamed.bytes));
+            // The page image is never built in memory, every write is a small piece of it
+            assert!(streamed.largest < 100, "{}", streamed.largest);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "page checksum mismatch")]
+    fn test_corrupted_page_deserialization() {
+        let page = page_with(vec![vec![DataType::Int32(10), DataType::Varchar("test".to_string())]]);
+        let mut serialized = page.serialize();
+        let last = serialized.len() - 10;
+        serialized[last] ^= 0xFF;
+        Page::deserialize(&serialized, &mut 0);
+    }
+
+    #[test]
+    fn test_btree_from_index_page() {
+        let header = Header::new(PageType::Index(DataType::Varchar("INDEX".to_string())), DataType::Int32(0), DataType::Int32(1), None);
+        let mut page = Page::new(header, None, None);
+        // Keys are appended out of order on purpose
+        for key in (0..60).rev() {
+            page.append_tuple(vec![DataType::Int32(key * 2), DataType::Int32(key + 100)]);
+        }
+        let btree = btree_from_index_page(&page, 3);
+        for key in 0..60 {
+            assert_eq!(btree.get(&(key * 2)), Some(&DataType::Int32(key + 100)));
+            assert_eq!(btree.get(&(key * 2 + 1)), None);
+        }
+    }
+
+    #[test]
+    fn test_build_index() {
+        let mut page = page_with(vec![]);
+        let mut ids = Vec::new();
+        for age in [40, 18, 65, 30] {
+            ids.push(page.append_tuple(vec![DataType::Varchar(format!("user{}", age)), DataType::Int32(age)]));
+        }
+        page.append_tuple(vec![DataType::Varchar("unknown".to_string()), DataType::Null]);
+        page.remove_tuple(ids[3]);
+
+        let index = build_index(&page, 1);
+        assert_eq!(index.get(&65), Some(&ids[2]));
+        assert_eq!(index.get(&18), Some(&ids[1]));
+        // Removed tuples and missing keys are not found
+        assert_eq!(index.get(&30), None);
+        assert_eq!(index.get(&99), None);
+    }
+
+    #[test]
+    fn test_get_tuple() {
+        let mut page = page_with(vec![]);
+        let tuple1 = vec![DataType::Int32(10), DataType::Varchar("test".to_string())];
+        let tuple2 = vec![DataType::Int32(20), DataType::Varchar("test2".to_string())];
+        page.append_tuple(tuple1.clone());
+        page.append_tuple(tuple2.clone());
+
+        assert_eq!(page.get_tuple(1), Some(&tuple1));
+        assert_eq!(page.get_tuple(2), Some(&tuple2));
+        assert_eq!(page.get_tuple(3), None);
+
+        page.remove_tuple(1);
+        assert_eq!(page.get_tuple(1), None);
+    }
+
+    // Helper to build a data page with the given tuples
+    fn page_with(tuples: Vec<Vec<DataType>>) -> Page {
+        let header = Header::new(PageType::Data(DataType::Varchar("DATA".to_string())), DataType::Int32(0), DataType::Int32(1), None);
+        let mut page = Page::new(header, None, None);
+        for tuple in tuples {
+            page.append_tuple(tuple);
+        }
+        page
+    }
+
+    // Checks that the page survives a round trip and that every tuple keeps its data
+    fn assert_round_trip(page: &Page) {
+        let deserialized = Page::deserialize(&page.serialize(), &mut 0);
+        assert_eq!(page.header.free_space, deserialized.header.free_space);
+        assert_eq!(page.data.len(), deserialized.data.len());
+        for slot in &page.slots {
+            let tuple_id = slot.tuple_id.as_int();
+            assert_eq!(page.get_tuple(tuple_id), deserialized.get_tuple(tuple_id));
+        }
+    }
+
+    #[test]
+    fn test_update_tuple_same_size() {
+        let mut page = page_with(vec![
+            vec![DataType::Int32(10), DataType::Varchar("test".to_string())],
+            vec![DataType::Int32(20), DataType::Varchar("test2".to_string())],
+        ]);
+        let free_space = page.get_free_space();
+        page.update_tuple(1, vec![DataType::Int32(11), DataType::Varchar("tset".to_string())]).unwrap();
+        assert_eq!(page.get_tuple(1), Some(&vec![DataType::Int32(11), DataType::Varchar("tset".to_string())]));
+        assert_eq!(page.get_free_space(), free_space);
+        assert_round_trip(&page);
+    }
+
+    #[test]
+    fn test_update_tuple_shrinking() {
+        let mut page = page_with(vec![
+            vec![DataType::Int32(10), DataType::Varchar("test".to_string()), DataType::Int32(1)],
+            vec![DataType::Int32(20), DataType::Varchar("test2".to_string())],
+        ]);
+        let free_space = page.get_free_space();
+        page.update_tuple(1, vec![DataType::Int32(10)]).unwrap();
+        assert_eq!(page.get_tuple(1), Some(&vec![DataType::Int32(10)]));
+        assert!(page.get_free_space() > free_space);
+        assert_round_trip(&page);
+    }
+
+    #[test]
+    fn test_update_tuple_growing_relocates() {
+        let mut page = page_with(vec![
+            vec![DataType::Int32(10)],
+            vec![DataType::Int32(20), DataType::Varchar("test2".to_string())],
+            vec![DataType::Int32(30)],
+        ]);
+        let free_space = page.get_free_space();
+        let grown = vec![DataType::Int32(10), DataType::Varchar("grown".to_string()), DataType::Bool(true)];
+        page.update_tuple(1, grown.clone()).unwrap();
+        assert_eq!(page.get_tuple(1), Some(&grown));
+        // The relocated tuple now starts the tuple block
+        assert_eq!(page.slot_for(1).unwrap().offset, page.header.offset);
+        assert!(page.get_free_space() < free_space);
+        assert_round_trip(&page);
+
+        assert!(page.update_tuple(4, vec![]).is_err());
+    }
+
+    #[test]
+    fn test_update_tuple_without_room() {
+        let mut page = page_with(vec![]);
+        page.header.set_free_space(DataType::Int32(100));
+        page.append_tuple(vec![DataType::Int32(10)]);
+        let wide = vec![DataType::Int32(10); 50];
+        assert!(page.update_tuple(1, wide).is_err());
+        assert_eq!(page.get_tuple(1), Some(&vec![DataType::Int32(10)]));
+    }
+
+    #[test]
+    fn test_verify_free_space() {
+        let mut page = page_with(vec![]);
+        assert!(page.verify_free_space());
+        for i in 1..=4 {
+            page.append_tuple(vec![DataType::Int32(i), DataType::Varchar(format!("test{}", i))]);
+            assert!(page.verify_free_space());
+        }
+        page.remove_tuple(1);
+        assert!(page.verify_free_space());
+        page.remove_tuple(3);
+        assert!(page.verify_free_space());
+        page.update_tuple(2, vec![DataType::Int32(2), DataType::Varchar("grown".to_string()), DataType::Bool(true)]).unwrap();
+        assert!(page.verify_free_space());
+        page.update_tuple(4, vec![DataType::Int32(4)]).unwrap();
+        assert!(page.verify_free_space());
+        page.append_tuple(vec![DataType::Int32(5)]);
+        assert!(page.verify_free_space());
+        page.compact();
+        assert!(page.verify_free_space());
+        for i in [1, 2, 3, 4] {
+            page.remove_tuple(i);
+            assert!(page.verify_free_space());
+        }
+        assert!(Page::deserialize(&page.serialize(), &mut 0).verify_free_space());
+
+        // A counter that drifted is detected
+        page.increase_free_space(1);
+        assert!(!page.verify_free_space());
+        assert_eq!(page.recompute_free_space(), page.get_free_space() - 1);
+    }
+
+    #[test]
+    fn test_compact() {
+        let mut page = page_with((1..=5).map(|i| vec![DataType::Int32(i), DataType::Varchar(format!("test{}", i))]).collect());
+        let free_space = page.get_free_space();
+        page.remove_tuple(2);
+        page.remove_tuple(4);
+        // Grow the first tuple so it is relocated out of slot order
+        page.update_tuple(1, vec![DataType::Int32(1), DataType::Varchar("test1".to_string()), DataType::Bool(true)]).unwrap();
+        let expected_free_space = page.get_free_space();
+
+        page.compact();
+        assert_eq!(page.get_free_space(), expected_free_space);
+        assert!(page.get_free_space() > free_space);
+        assert_eq!(page.header.offset, page.slots.back().unwrap().offset);
+
+        // Tuples are laid out in slot order without gaps nor overlaps
+        let ids: Vec<i32> = page.data.iter().rev().map(|tuple| tuple.tuple_id.as_int()).collect();
+        assert_eq!(ids, vec![1, 3, 5]);
+        let mut end = page.page_size() - 1;
+        for slot in page.slots.iter().filter(|slot| !slot.deleted) {
+            assert_eq!(slot.offset.as_int() + slot.length.as_int(), end);
+            end = slot.offset.as_int();
+        }
+        assert_round_trip(&page);
+        let deserialized = Page::deserialize(&page.serialize(), &mut 0);
+        assert_eq!(deserialized.get_tuple(5), Some(&vec![DataType::Int32(5), DataType::Varchar("test5".to_string())]));
+    }
+
     #[test]
     fn test_multiple_tuples(){
 
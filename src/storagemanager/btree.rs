@@ -1,52 +1,62 @@
 use std::cmp::Ordering;
 
+use crate::storagemanager::serialization::DataType;
+
 // An entry of a node.
 // It contains a key and a value.
-// The key is used to sort the entries in the node.
+// The key is used to sort the entries in the node, so it can be any ordered type.
 // The value can be any type, so it is generic.
 #[derive(Debug, Clone)]
-pub struct Entry<T> {
-    key: i32,
+pub struct Entry<K, T> {
+    key: K,
     value: T,
 }
 
-impl<T> Entry<T> {
-    pub fn new(key: i32, value: T) -> Self {
+impl<K, T> Entry<K, T> {
+    pub fn new(key: K, value: T) -> Self {
         Entry { key, value }
     }
 }
 
 // Implement the PartialEq trait for the Entry struct.
-impl<T> PartialEq for Entry<T> {
+impl<K: Ord, T> PartialEq for Entry<K, T> {
     fn eq(&self, other: &Self) -> bool {
         self.key == other.key
     }
 }
 
 // Implement the PartialOrd trait for the Entry struct.
-impl<T> PartialOrd for Entry<T> {
+impl<K: Ord, T> PartialOrd for Entry<K, T> {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
         Some(self.key.cmp(&other.key))
     }
 }
 
+// Key made of the values of several columns, e.g. (last_name, first_name).
+// Keys are compared column by column, so the second value only breaks ties on the first one.
+// No multi-column index is built yet, so only the tests construct it.
+#[allow(dead_code)]
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct CompositeKey(pub Vec<DataType>);
+
 // A node of a B-Tree.
 // It contains a list of entries and a list of children.
 #[derive(Debug, Clone)]
-struct BTreeNode<T>
+pub struct BTreeNode<K, T>
 where
+    K: Clone,
     T: Clone,
 {
-    entries: Vec<Entry<T>>,
-    children: Vec<BTreeNode<T>>,
+    entries: Vec<Entry<K, T>>,
+    children: Vec<BTreeNode<K, T>>,
     is_leaf: bool,
     is_root: bool,
 }
 
-impl<T: std::clone::Clone> BTreeNode<T> {
+impl<K: Ord + Clone, T: std::clone::Clone> BTreeNode<K, T> {
     pub fn new(
-        entries: Option<Vec<Entry<T>>>,
-        children: Option<Vec<BTreeNode<T>>>,
+        entries: Option<Vec<Entry<K, T>>>,
+        children: Option<Vec<BTreeNode<K, T>>>,
         is_leaf: bool,
         is_root: bool,
     ) -> Self {
@@ -66,20 +76,20 @@ impl<T: std::clone::Clone> BTreeNode<T> {
         self.entries.len() < degree as usize - 1
     }
 
-    pub fn get_predecessor(&self, key: i32) -> &Entry<T> {
+    pub fn get_predecessor(&self, key: &K) -> &Entry<K, T> {
         // From the key, find previous entry in the node
         let mut i = 0;
-        while i < self.entries.len() && key < self.entries[i].key {
+        while i < self.entries.len() && *key < self.entries[i].key {
             i += 1;
         }
         &self.entries[i - 1]
     }
 
-    pub fn get_successor(&self, key: i32) -> &Entry<T> {
+    pub fn get_successor(&self, key: &K) -> &Entry<K, T> {
         // From the key, find the key of the predecessor child
 
         let mut i = 0;
-        while i < self.entries.len() && key < self.entries[i].key {
+        while i < self.entries.len() && *key < self.entries[i].key {
             i += 1;
         }
         &self.entries[i]
@@ -91,28 +101,83 @@ impl<T: std::clone::Clone> BTreeNode<T> {
 }
 
 #[derive(Debug)]
-struct BTree<T: std::clone::Clone> {
-    root: BTreeNode<T>,
+pub struct BTree<K: Ord + Clone, T: std::clone::Clone> {
+    root: BTreeNode<K, T>,
     degree: i32,
 }
 
-impl<T: std::clone::Clone> BTree<T> {
-    pub fn new(root: Option<BTreeNode<T>>, degree: i32) -> Self {
+impl<K: Ord + Clone, T: std::clone::Clone> BTree<K, T> {
+    pub fn new(root: Option<BTreeNode<K, T>>, degree: i32) -> Self {
         BTree {
             root: root.unwrap_or(BTreeNode::new(None, None, true, true)),
             degree,
         }
     }
 
-    pub fn search<'a>(&'a self, u: &'a BTreeNode<T>, key: i32) -> Option<&'a Entry<T>> {
+    // Builds a B-Tree from a list of entries without inserting them one by one.
+    // The entries are sorted and packed into leaves, and the keys between two leaves are promoted
+    // as separators into the level above. This is repeated until a single root remains,
+    // so all the leaves are at the same depth and every non root node has at least t - 1 entries.
+    // The minimum degree of a B-Tree is 2.
+    pub fn bulk_load(mut entries: Vec<Entry<K, T>>, degree: i32) -> Self {
+        assert!(degree >= 2, "The minimum degree of a B-Tree is 2, got {}", degree);
+        entries.sort_by(|a, b| a.key.cmp(&b.key));
+        let t = degree as usize;
+        let n = entries.len();
+        if n < 2 * t {
+            return BTree::new(Some(BTreeNode::new(Some(entries), None, true, true)), degree);
+        }
+
+        // Leaf level: g leaves hold n - (g - 1) entries, the remaining g - 1 are separators
+        let g = (n + 1).div_ceil(2 * t);
+        let mut nodes = Vec::new();
+        let mut separators = Vec::new();
+        let mut remaining = entries.into_iter();
+        for size in Self::group_sizes(n - (g - 1), g) {
+            let leaf_entries: Vec<Entry<K, T>> = remaining.by_ref().take(size).collect();
+            nodes.push(BTreeNode::new(Some(leaf_entries), None, true, false));
+            separators.extend(remaining.next());
+        }
+
+        // Internal levels: group the children under parents of at most 2t children
+        while nodes.len() > 2 * t {
+            let h = nodes.len().div_ceil(2 * t);
+            let mut parents = Vec::new();
+            let mut promoted = Vec::new();
+            let mut children = nodes.into_iter();
+            let mut keys = separators.into_iter();
+            for size in Self::group_sizes(children.len(), h) {
+                let node_children: Vec<BTreeNode<K, T>> = children.by_ref().take(size).collect();
+                let node_entries: Vec<Entry<K, T>> = keys.by_ref().take(size - 1).collect();
+                parents.push(BTreeNode::new(Some(node_entries), Some(node_children), false, false));
+                promoted.extend(keys.next());
+            }
+            nodes = parents;
+            separators = promoted;
+        }
+
+        BTree::new(Some(BTreeNode::new(Some(separators), Some(nodes), false, true)), degree)
+    }
+
+    // Splits `items` into `groups` sizes that differ by at most one
+    fn group_sizes(items: usize, groups: usize) -> Vec<usize> {
+        (0..groups).map(|i| items / groups + usize::from(i < items % groups)).collect()
+    }
+
+    // Looks up a key starting from the root
+    pub fn get(&self, key: &K) -> Option<&T> {
+        self.search(&self.root, key).map(|entry| &entry.value)
+    }
+
+    pub fn search<'a>(&'a self, u: &'a BTreeNode<K, T>, key: &K) -> Option<&'a Entry<K, T>> {
         // Linear search for the key in the node
         let mut i = 0;
-        while i < u.entries.len() && key > u.entries[i].key {
+        while i < u.entries.len() && *key > u.entries[i].key {
             i += 1;
         }
 
         // If the key is found, return the entry
-        if i < u.entries.len() && key == u.entries[i].key {
+        if i < u.entries.len() && *key == u.entries[i].key {
             return Some(&u.entries[i]);
         }
 
@@ -127,7 +192,7 @@ impl<T: std::clone::Clone> BTree<T> {
 
     // Inserts a new entry into the B-Tree on a non-full node
     // FIX-THEN-PROCEED strategy
-    pub fn insert_non_full(&self, u: &mut BTreeNode<T>, key: i32, value: T) {
+    pub fn insert_non_full(&self, u: &mut BTreeNode<K, T>, key: K, value: T) {
         let mut i = 0;
         while i < u.entries.len() && key > u.entries[i].key {
             i += 1;
@@ -143,43 +208,64 @@ impl<T: std::clone::Clone> BTree<T> {
                 if key > u.entries[i].key {
                     i += 1;
                 }
-                self.insert_non_full(&mut u.children[i], key, value);
             }
+            self.insert_non_full(&mut u.children[i], key, value);
+        }
+    }
+
+    // Inserts a new entry starting from the root
+    // A full root is split first, so the descent only meets non-full nodes
+    pub fn insert(&mut self, key: K, value: T) {
+        if self.root.is_full(self.degree) {
+            self.split_root();
         }
+        let mut root = std::mem::replace(&mut self.root, BTreeNode::new(None, None, true, true));
+        self.insert_non_full(&mut root, key, value);
+        self.root = root;
     }
 
     // Helper function to split the root node when full
     // Creates a new root node with the old root as its child
     // This is the only case where the height of the tree increases
-    pub fn split_root(&self) -> BTreeNode<T> {
-        let root = self.root.clone();
-        let t = self.degree;
-        let mut new = BTreeNode::new(None, None, false, false);
+    pub fn split_root(&mut self) {
+        let mut root = std::mem::replace(&mut self.root, BTreeNode::new(None, None, true, true));
+        root.is_root = false;
+        let mut new = BTreeNode::new(None, None, false, true);
         new.children.push(root);
         self.split_child(&mut new, 0);
-        new
+        self.root = new;
     }
 
-    pub fn split_child(&self, u: &mut BTreeNode<T>, i: usize) {
-        let mut z = u.children[i].clone();
-        let t = self.degree;
+    // Number of levels of the tree, a single leaf root has height 1
+    // All the leaves are at the same depth, so following the first child is enough
+    pub fn height(&self) -> usize {
+        let mut height = 1;
+        let mut node = &self.root;
+        while !node.is_leaf {
+            node = &node.children[0];
+            height += 1;
+        }
+        height
+    }
 
-        let mut new = BTreeNode::new(None, None, z.is_leaf, false);
-        new.entries.extend_from_slice(&z.entries[t as usize..]);
+    // Splits the full i-th child of u around its median entry, which moves up into u
+    pub fn split_child(&self, u: &mut BTreeNode<K, T>, i: usize) {
+        let t = self.degree as usize;
+        let z = &mut u.children[i];
 
+        let mut new = BTreeNode::new(Some(z.entries.split_off(t)), None, z.is_leaf, false);
         if !z.is_leaf {
-            new.children = z.children.split_off(t as usize);
+            new.children = z.children.split_off(t);
         }
+        let median = z.entries.pop().unwrap(); // median entry
         u.children.insert(i + 1, new);
-        u.entries.insert(i, z.entries[t as usize - 1].clone()); // median entry
-        z.entries.truncate(t as usize - 1);
-        z.children.truncate(t as usize);
+        u.entries.insert(i, median);
         // self.write_to_disk(&z);
         // self.write_to_disk(&new);
         // self.write_to_disk(&u);
     }
 
-    pub fn merge_children(&self, u: &mut BTreeNode<T>, i: usize) {
+    pub fn merge_children(&self, u: &mut BTreeNode<K, T>, i: usize) {
         // Merge the i-th child of the node u with its i+1-th sibling
 
         let t = self.degree;
@@ -200,7 +286,7 @@ impl<T: std::clone::Clone> BTree<T> {
     // 1. The key is in the node u and is a leaf
     // 2. The key is in the node u and is an internal node
     // 3. The key is not in the node u
-    pub fn delete(&mut self, u: &mut BTreeNode<T>, key: i32) {
+    pub fn delete(&mut self, u: &mut BTreeNode<K, T>, key: K) {
         // Assumption: u has at least t keys or is the root
         let t = self.degree;
         let mut i = 0;
@@ -224,8 +310,8 @@ impl<T: std::clone::Clone> BTree<T> {
             if u.children[i].entries.len() >= t as usize {
                 // Find the predecessor entry
                 let mut child = &u.children[i];
-                let pred_entry = child.get_predecessor(key).clone();
-                let pred_key = pred_entry.key;
+                let pred_entry = child.get_predecessor(&key).clone();
+                let pred_key = pred_entry.key.clone();
                 // Call delete on the predecessor child
                 self.delete(&mut u.children[i], pred_key);
                 u.entries[i] = pred_entry;
@@ -234,8 +320,8 @@ impl<T: std::clone::Clone> BTree<T> {
             else if u.children[i + 1].entries.len() >= t as usize {
                 // Find the successor entry
                 let mut child = &u.children[i + 1];
-                let succ_entry = child.get_successor(key).clone();
-                let succ_key = succ_entry.key;
+                let succ_entry = child.get_successor(&key).clone();
+                let succ_key = succ_entry.key.clone();
                 // Call delete on the successor child
                 self.delete(&mut u.children[i + 1], succ_key);
                 u.entries[i] = succ_entry;
@@ -323,7 +409,7 @@ fn main() {
     println!("{:?}", btree);
     let mut root = btree.root.clone();
 
-    let found = btree.search(&root, 2);
+    let found = btree.search(&root, &2);
 
     match found {
         Some(entry) => println!("Found: {:?}", entry),
@@ -333,7 +419,7 @@ fn main() {
     let entry4 = Entry::new(4, "Hola");
     btree.insert_non_full(&mut root, 4, "Hola");
     println!("{:?}", btree);
-    let found = btree.search(&root, 4);
+    let found = btree.search(&root, &4);
 
     match found {
         Some(entry) => println!("Found: {:?}", entry),
@@ -342,7 +428,7 @@ fn main() {
 
     btree.insert_non_full(&mut root, 12, "Hola");
     println!("{:?}", btree);
-    let found = btree.search(&root, 12);
+    let found = btree.search(&root, &12);
 
     match found {
         Some(entry) => println!("Found: {:?}", entry),
@@ -351,7 +437,7 @@ fn main() {
 
     btree.insert_non_full(&mut root, 13, "Hola");
     println!("{:?}", btree);
-    let found = btree.search(&root, 13);
+    let found = btree.search(&root, &13);
 
     match found {
         Some(entry) => println!("Found: {:?}", entry),
@@ -359,7 +445,7 @@ fn main() {
     }
     btree.insert_non_full(&mut root, 12, "Hola");
     println!("{:?}", btree);
-    let found = btree.search(&root, 12);
+    let found = btree.search(&root, &12);
 
     match found {
         Some(entry) => println!("Found: {:?}", entry),
@@ -368,7 +454,7 @@ fn main() {
 
     btree.insert_non_full(&mut root, 5, "Hola");
     println!("{:?}", btree);
-    let found = btree.search(&root, 5);
+    let found = btree.search(&root, &5);
 
     match found {
         Some(entry) => println!("Found: {:?}", entry),
@@ -377,7 +463,7 @@ fn main() {
 
     btree.insert_non_full(&mut root, 6, "Hola");
     println!("{:?}", btree);
-    let found = btree.search(&root, 6);
+    let found = btree.search(&root, &6);
 
     match found {
         Some(entry) => println!("Found: {:?}", entry),
@@ -386,10 +472,76 @@ fn main() {
 
     btree.delete(&mut root, 6);
     println!("{:?}", btree);
-    let found = btree.search(&root, 6);
+    let found = btree.search(&root, &6);
 
     match found {
         Some(entry) => println!("Found: {:?}", entry),
         None => println!("Not found"),
     }
 }
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_insert_splits_root() {
+        let mut btree = BTree::new(None, 2);
+        for key in 1..=3 {
+            btree.insert(key, key * 10);
+        }
+        assert!(btree.root.is_leaf);
+        assert_eq!(btree.height(), 1);
+
+        // The root is full, so the next insert splits it
+        btree.insert(4, 40);
+        assert!(!btree.root.is_leaf);
+        assert!(btree.root.is_root);
+        assert!(btree.root.children.iter().all(|child| !child.is_root));
+        assert_eq!(btree.height(), 2);
+        assert_eq!(btree.root.entries.len(), 1);
+    }
+
+    #[test]
+    fn test_insert_many() {
+        let mut btree = BTree::new(None, 2);
+        let keys: Vec<i32> = (0..200).map(|i| (i * 37) % 200).collect();
+        for key in &keys {
+            btree.insert(*key, key.to_string());
+        }
+        assert!(btree.height() > 2);
+        for key in &keys {
+            assert_eq!(btree.get(key), Some(&key.to_string()));
+        }
+        assert_eq!(btree.get(&200), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "minimum degree")]
+    fn test_bulk_load_rejects_small_degree() {
+        BTree::bulk_load(vec![Entry::new(1, 1)], 0);
+    }
+
+    #[test]
+    fn test_composite_key() {
+        let key = |last: &str, first: &str| CompositeKey(vec![DataType::Varchar(last.to_string()), DataType::Varchar(first.to_string())]);
+        assert!(key("Smith", "Anna") < key("Smith", "John"));
+        assert!(key("Doe", "Zoe") < key("Smith", "Anna"));
+
+        let mut btree: BTree<CompositeKey, i32> = BTree::new(None, 2);
+        let names = [("Smith", "John"), ("Doe", "Jane"), ("Smith", "Anna"), ("Brown", "Bob"), ("Doe", "Adam")];
+        for (id, (last, first)) in names.iter().enumerate() {
+            btree.insert(key(last, first), id as i32);
+        }
+        assert_eq!(btree.get(&key("Smith", "Anna")), Some(&2));
+        assert_eq!(btree.get(&key("Doe", "Adam")), Some(&4));
+        assert_eq!(btree.get(&key("Smith", "Jane")), None);
+
+        // Bulk loading sorts by the first column and then by the second
+        let entries = names.iter().enumerate().map(|(id, (last, first))| Entry::new(key(last, first), id as i32)).collect();
+        let loaded: BTree<CompositeKey, i32> = BTree::bulk_load(entries, 3);
+        let order: Vec<i32> = loaded.root.entries.iter().map(|entry| entry.value).collect();
+        assert_eq!(order, vec![3, 4, 1, 2, 0]);
+    }
+}
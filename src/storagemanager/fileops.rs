@@ -4,28 +4,67 @@
 
 // There are two types of files in the storage manager:
 // 1. Small files that can fit in memory
-// 2. Large files that are stored on disk and should be read through a buffer pool (not implemented yet).
+// 2. Large files that are stored on disk and should be read through a buffer pool (see bufferpool.rs).
 // The ManagedFile struct is a wrapper for both types of files. It implements the SmallFile trait for small files, and the LargeFile trait for large files. Dependending on the file size, the storage manager will choose the appropriate file type.
 
-use std::fs::File;
+use std::fs::{File, OpenOptions};
 use std::io::{Read, Write, Seek, SeekFrom, Result, Error, ErrorKind};
 
 
+const SMALL_FILE_LIMIT: u64 = 64 * 1024 * 1024; // LARGEST FILE READ INTO MEMORY BY DEFAULT (64 MiB)
+const FILE_MAGIC: [u8; 4] = *b"RGQL"; // FIRST BYTES OF THE CATALOG AND DIRECTORY FILES
+const FORMAT_VERSION: u8 = 1; // VERSION OF THEIR ON-DISK LAYOUT, BUMPED WHENEVER IT CHANGES
+
+
+// Header written at the beginning of the catalog and directory files: the magic number and the format version
+pub fn file_header() -> Vec<u8> {
+    let mut header = FILE_MAGIC.to_vec();
+    header.push(FORMAT_VERSION);
+    header
+}
+
+// Checks the header of a catalog or directory file and moves the offset past it.
+// Files written before the header existed, or in another version of the layout, are rejected instead of being misread.
+pub fn check_file_header(buffer: &[u8], offset: &mut usize) -> Result<()> {
+    if buffer.get(*offset..*offset + FILE_MAGIC.len()) != Some(&FILE_MAGIC[..]) {
+        return Err(Error::new(ErrorKind::InvalidData, "Not a RustgresQL file or written by a version without format header"));
+    }
+    let version = buffer.get(*offset + FILE_MAGIC.len()).copied();
+    if version != Some(FORMAT_VERSION) {
+        return Err(Error::new(ErrorKind::InvalidData, format!("Unsupported format version {:?}, expected {}", version, FORMAT_VERSION)));
+    }
+    *offset += FILE_MAGIC.len() + 1;
+    Ok(())
+}
+
+
 // Trait for reading and writing small files that can fit in memory
 pub trait SmallFile {
     fn read_to_end(&self) -> Result<Vec<u8>>;
     fn write_all(&self, buf: &[u8]) -> Result<()>;
+    fn append_all(&self, buf: &[u8]) -> Result<()>;
 }
 
 pub trait LargeFile {
    
     fn read(&self, offset: usize, size: usize) -> Result<Vec<u8>>;
     fn write(&self, offset: usize, buf: &[u8]) -> Result<()>;
+
+    // Page-addressed access: page n lives at n * page_size
+    fn read_page(&self, page_no: u64, page_size: usize) -> Result<Vec<u8>> {
+        self.read(page_no as usize * page_size, page_size)
+    }
+
+    // The page size is the length of the buffer
+    fn write_page(&self, page_no: u64, buf: &[u8]) -> Result<()> {
+        self.write(page_no as usize * buf.len(), buf)
+    }
 }
 // Struct of a file that can be read and written by the storage manager
 #[derive(Debug)]
 pub struct ManagedFile {
     path: String,
+    small_file_limit: u64, // Files bigger than this are refused by read_to_end
 }
 
 // Implementation of the ManagedFile struct
@@ -33,13 +72,32 @@ impl ManagedFile {
     pub fn new(path: &str) -> Self {
         Self {
             path: path.to_string(),
+            small_file_limit: SMALL_FILE_LIMIT,
+        }
+    }
+
+    pub fn set_small_file_limit(&mut self, limit: u64) {
+        self.small_file_limit = limit;
+    }
+
+    // Creates the directories the file lives in, so the first write does not fail
+    fn create_parent_dirs(&self) -> Result<()> {
+        match std::path::Path::new(&self.path).parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => std::fs::create_dir_all(parent),
+            _ => Ok(()),
         }
     }
 
     fn open_file(&self, mode: &str) -> Result<File> {
+        if mode != "r" {
+            self.create_parent_dirs()?;
+        }
         match mode {
             "r" => File::open(&self.path),
             "w" => File::create(&self.path),
+            "a" => OpenOptions::new().append(true).create(true).open(&self.path),
+            // Read and write in place, without truncating the file
+            "rw" => OpenOptions::new().read(true).write(true).create(true).truncate(false).open(&self.path),
             _ => Err(Error::new(ErrorKind::InvalidInput, "Invalid mode")),
         }
     }
@@ -59,7 +117,7 @@ impl LargeFile for ManagedFile {
     }
 
     fn write(&self, offset: usize, buf: &[u8]) -> Result<()> {
-        let mut file = self.open_file("w")?;
+        let mut file = self.open_file("rw")?;
         // Spin the disk to the desired offset
         file.seek(SeekFrom::Start(offset as u64))?;
         file.write_all(buf)?;
@@ -73,8 +131,11 @@ impl SmallFile for ManagedFile {
     fn read_to_end(&self) -> Result<Vec<u8>> {
        
         let mut file = self.open_file("r")?;
-        // Check if we can fit the file in memory
-        assert!(file.metadata()?.len() <= usize::MAX as u64, "File too large to fit in memory");
+        // Files over the limit should go through the large file path instead
+        let len = file.metadata()?.len();
+        if len > self.small_file_limit {
+            return Err(Error::other(format!("File of {} bytes exceeds the small file limit of {} bytes", len, self.small_file_limit)));
+        }
         let mut buffer = Vec::new();
         file.read_to_end(&mut buffer)?;
         Ok(buffer)
@@ -86,4 +147,102 @@ impl SmallFile for ManagedFile {
         let mut file = self.open_file("w")?;
         file.write_all(buf)
     }
-}
\ No newline at end of file
+
+    // Writes at the end of the file, keeping what was already there
+    fn append_all(&self, buf: &[u8]) -> Result<()> {
+        let mut file = self.open_file("a")?;
+        file.write_all(buf)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Builds a path in the temp dir and removes any leftover file
+    fn temp_path(name: &str) -> String {
+        let path = std::env::temp_dir().join(name);
+        let _ = std::fs::remove_file(&path);
+        path.to_str().unwrap().to_string()
+    }
+
+    #[test]
+    fn test_read_write_page() {
+        let path = temp_path("rustgresql_test_pages.db");
+        let file = ManagedFile::new(&path);
+        let first = vec![1u8; 64];
+        let third = vec![3u8; 64];
+        file.write_page(2, &third).unwrap();
+        file.write_page(0, &first).unwrap();
+
+        assert_eq!(file.read_page(0, 64).unwrap(), first);
+        assert_eq!(file.read_page(2, 64).unwrap(), third);
+        // The gap between both pages is left zeroed
+        assert_eq!(file.read_page(1, 64).unwrap(), vec![0u8; 64]);
+        assert!(file.read_page(3, 64).is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_append_all() {
+        let path = temp_path("rustgresql_test_append.db");
+        let file = ManagedFile::new(&path);
+        file.write_all(b"first").unwrap();
+        file.append_all(b" second").unwrap();
+        file.append_all(b" third").unwrap();
+        assert_eq!(file.read_to_end().unwrap(), b"first second third");
+
+        // Writing again still replaces the contents
+        file.write_all(b"new").unwrap();
+        assert_eq!(file.read_to_end().unwrap(), b"new");
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_write_creates_parent_dirs() {
+        let root = std::env::temp_dir().join("rustgresql_test_nested");
+        let _ = std::fs::remove_dir_all(&root);
+        let path = root.join("some/nested/new/dir/file.db");
+        let file = ManagedFile::new(path.to_str().unwrap());
+        file.write_all(b"data").unwrap();
+        assert!(path.exists());
+        assert_eq!(file.read_to_end().unwrap(), b"data");
+
+        let appended = ManagedFile::new(root.join("other/dir/log.db").to_str().unwrap());
+        appended.append_all(b"entry").unwrap();
+        assert!(root.join("other/dir/log.db").exists());
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_file_header() {
+        let mut buffer = file_header();
+        buffer.push(42);
+        let mut offset = 0;
+        check_file_header(&buffer, &mut offset).unwrap();
+        assert_eq!(buffer[offset], 42);
+
+        // A file without the header, e.g. one written before it existed
+        assert_eq!(check_file_header(&[0x02, 0, 0, 0, 0, 0], &mut 0).unwrap_err().kind(), ErrorKind::InvalidData);
+        assert!(check_file_header(&[], &mut 0).is_err());
+        // Another version of the layout
+        let mut newer = file_header();
+        newer[FILE_MAGIC.len()] = FORMAT_VERSION + 1;
+        assert_eq!(check_file_header(&newer, &mut 0).unwrap_err().kind(), ErrorKind::InvalidData);
+        assert!(check_file_header(&FILE_MAGIC, &mut 0).is_err());
+    }
+
+    #[test]
+    fn test_read_to_end_limit() {
+        let path = temp_path("rustgresql_test_limit.db");
+        let mut file = ManagedFile::new(&path);
+        file.write_all(&[7u8; 32]).unwrap();
+        assert_eq!(file.read_to_end().unwrap().len(), 32);
+
+        file.set_small_file_limit(16);
+        let error = file.read_to_end().unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::Other);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
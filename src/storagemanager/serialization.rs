@@ -1,43 +1,184 @@
+use std::cmp::Ordering;
 use std::hash::{Hash, Hasher};
+use std::io::{self, Write};
 
-const ENDIANESS:bool = cfg!(target_endian = "little"); // True if little endian, false if big endian
+// Every multi-byte value is stored little-endian, regardless of the host
 const MAX_INT4_SIZE: usize = 4; // 4 bytes for a 32-bit integer
+const MAX_INT8_SIZE: usize = 8; // 8 bytes for a 64-bit integer
 const MAX_FLOAT_SIZE: usize = 8; // 8 bytes for a 64-bit float
-const MAX_STR_SIZE: usize = 32; // 32 bytes for a text field
+const STR_LEN_SIZE: usize = 4; // 4 bytes for the length prefix of a text field
 const BOOLEAN_SIZE: usize = 1; // 1 byte for a boolean
+const UUID_SIZE: usize = 16; // 16 bytes for a UUID
+const SECONDS_PER_DAY: i64 = 86_400;
+const MICROS_PER_SECOND: i64 = 1_000_000;
+const MAX_YEAR: i32 = 9999; // LAST YEAR ACCEPTED BY THE CHECKED DATE CONSTRUCTORS, THE FOUR DIGITS as_string WRITES
 
 
 
 
 
 
-#[derive(Debug, Clone, PartialEq,)]
+#[derive(Debug, Clone,)]
 pub enum DataType {
-    /// Represents a variable-length character string with a maximum length of 255 characters.
-    Varchar(String),  // Varchar with a maximum length of 255 characters
+    /// Represents a variable-length character string.
+    Varchar(String),  // Varchar stored as a 4 byte length followed by its bytes
     Int32(i32),           // INT32 4 bytes
+    Int64(i64),           // INT64 8 bytes
+    Date(i32),            // DATE as days since 1970-01-01, 4 bytes
+    Timestamp(i64),       // TIMESTAMP as microseconds since 1970-01-01 00:00:00, 8 bytes
+    Uuid([u8; 16]),       // UUID 16 bytes
     Float64(f64),         // FLOAT64  8 bytes
+    Decimal { mantissa: i64, scale: u8 }, // DECIMAL as mantissa / 10^scale, 9 bytes
     Bool(bool),           // BOOL 1 byte
     Null,                 // NULL represented as  bitmap of 1 byte
 }
 
 
 impl DataType {
+    // Builds a date from its calendar components
+    // Panics on a date that does not exist, so input that is not known to be valid goes through try_date
+    pub fn date(year: i32, month: u32, day: u32) -> DataType {
+        DataType::try_date(year, month, day).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    // Builds a date, checking that the month exists and that the day exists in that month
+    // Years go from 0 to 9999
+    pub fn try_date(year: i32, month: u32, day: u32) -> Result<DataType, String> {
+        Ok(DataType::Date(checked_days_from_civil(year, month, day)?))
+    }
+
+    // Builds a timestamp from its calendar and clock components
+    // Panics on an invalid date or time, so input that is not known to be valid goes through try_timestamp
+    pub fn timestamp(year: i32, month: u32, day: u32, hour: u32, minute: u32, second: u32, micros: u32) -> DataType {
+        DataType::try_timestamp(year, month, day, hour, minute, second, micros).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    // Builds a timestamp, checking the date as try_date does and that the time of day is in range
+    pub fn try_timestamp(year: i32, month: u32, day: u32, hour: u32, minute: u32, second: u32, micros: u32) -> Result<DataType, String> {
+        let days = checked_days_from_civil(year, month, day)?;
+        if hour >= 24 || minute >= 60 || second >= 60 || micros as i64 >= MICROS_PER_SECOND {
+            return Err(format!("Invalid time: {:02}:{:02}:{:02}.{:06}", hour, minute, second, micros));
+        }
+        let seconds = days as i64 * SECONDS_PER_DAY + (hour * 3600 + minute * 60 + second) as i64;
+        Ok(DataType::Timestamp(seconds * MICROS_PER_SECOND + micros as i64))
+    }
+
+    // Midnight of the given date
+    pub fn timestamp_from_date(days: i32) -> DataType {
+        DataType::Timestamp(days as i64 * SECONDS_PER_DAY * MICROS_PER_SECOND)
+    }
+
+    // Date a timestamp falls in, dropping the time of day
+    pub fn to_date(&self) -> DataType {
+        match self {
+            DataType::Timestamp(micros) => {
+                DataType::Date(micros.div_euclid(SECONDS_PER_DAY * MICROS_PER_SECOND) as i32)
+            }
+            DataType::Date(days) => DataType::Date(*days),
+            _ => panic!("Cannot convert to date"),
+        }
+    }
+
+    // Parses a UUID in its canonical hyphenated form, e.g. 123e4567-e89b-12d3-a456-426614174000
+    pub fn uuid(value: &str) -> Result<DataType, String> {
+        let groups: Vec<&str> = value.split('-').collect();
+        let lengths: Vec<usize> = groups.iter().map(|group| group.len()).collect();
+        if lengths != [8, 4, 4, 4, 12] || !groups.iter().all(|group| group.chars().all(|c| c.is_ascii_hexdigit())) {
+            return Err(format!("Invalid UUID: {}", value));
+        }
+        let hex = groups.concat();
+        let mut bytes = [0u8; 16];
+        for (i, byte) in bytes.iter_mut().enumerate() {
+            *byte = u8::from_str_radix(&hex[2 * i..2 * i + 2], 16).unwrap();
+        }
+        Ok(DataType::Uuid(bytes))
+    }
+
+    // Parses a decimal literal such as "12.34" or "-0.5".
+    // The scale is the number of digits after the point.
+    pub fn decimal(value: &str) -> Result<DataType, String> {
+        let (negative, digits) = match value.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, value),
+        };
+        let (integer, fraction) = digits.split_once('.').unwrap_or((digits, ""));
+        if integer.is_empty() && fraction.is_empty()
+            || !integer.chars().chain(fraction.chars()).all(|c| c.is_ascii_digit()) {
+            return Err(format!("Invalid decimal: {}", value));
+        }
+        let scale = u8::try_from(fraction.len()).map_err(|_| format!("Invalid decimal: {}", value))?;
+        let mantissa: i64 = format!("{}{}", integer, fraction).parse()
+            .map_err(|_| format!("Decimal out of range: {}", value))?;
+        let mantissa = if negative { -mantissa } else { mantissa };
+        Ok(DataType::Decimal { mantissa, scale })
+    }
+
     pub fn get_type(&self) -> u8 {
         match self {
             DataType::Varchar(_) => 0x01,
             DataType::Int32(_) => 0x02,
             DataType::Float64(_) => 0x03,
             DataType::Bool(_) => 0x04,
+            DataType::Int64(_) => 0x05,
+            DataType::Date(_) => 0x06,
+            DataType::Decimal { .. } => 0x07,
+            DataType::Timestamp(_) => 0x08,
+            DataType::Uuid(_) => 0x09,
             DataType::Null => 0x00,
         }
     }
 
+    // SQL name of the type of the value
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            DataType::Varchar(_) => "VARCHAR",
+            DataType::Int32(_) => "INT",
+            DataType::Int64(_) => "BIGINT",
+            DataType::Date(_) => "DATE",
+            DataType::Timestamp(_) => "TIMESTAMP",
+            DataType::Uuid(_) => "UUID",
+            DataType::Float64(_) => "FLOAT",
+            DataType::Decimal { .. } => "DECIMAL",
+            DataType::Bool(_) => "BOOL",
+            DataType::Null => "NULL",
+        }
+    }
+
     pub fn as_string(&self) -> String {
         match self {
             DataType::Varchar(value) => value.clone(),
             DataType::Int32(value) => value.to_string(),
+            DataType::Int64(value) => value.to_string(),
+            DataType::Date(days) => {
+                let (year, month, day) = civil_from_days(*days);
+                format!("{:04}-{:02}-{:02}", year, month, day)
+            }
+            DataType::Timestamp(micros) => {
+                // ISO-8601, e.g. 2024-01-31T13:45:00.000000
+                let seconds = micros.div_euclid(MICROS_PER_SECOND);
+                let fraction = micros.rem_euclid(MICROS_PER_SECOND);
+                let (year, month, day) = civil_from_days(seconds.div_euclid(SECONDS_PER_DAY) as i32);
+                let time = seconds.rem_euclid(SECONDS_PER_DAY);
+                format!("{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:06}",
+                    year, month, day, time / 3600, time % 3600 / 60, time % 60, fraction)
+            }
+            DataType::Uuid(bytes) => {
+                let hex: String = bytes.iter().map(|byte| format!("{:02x}", byte)).collect();
+                format!("{}-{}-{}-{}-{}", &hex[0..8], &hex[8..12], &hex[12..16], &hex[16..20], &hex[20..32])
+            }
             DataType::Float64(value) => value.to_string(),
+            DataType::Decimal { mantissa, scale } => {
+                // Pad with zeros so there is at least one digit before the point
+                let scale = *scale as usize;
+                let digits = format!("{:0width$}", mantissa.unsigned_abs(), width = scale + 1);
+                let sign = if *mantissa < 0 { "-" } else { "" };
+                if scale == 0 {
+                    format!("{}{}", sign, digits)
+                } else {
+                    let (integer, fraction) = digits.split_at(digits.len() - scale);
+                    format!("{}{}.{}", sign, integer, fraction)
+                }
+            }
             DataType::Bool(value) => value.to_string(),
             DataType::Null => "NULL".to_string(),
         }
@@ -50,6 +191,15 @@ impl DataType {
         }
     }
 
+    pub fn as_i64(&self) -> i64 {
+        match self {
+            DataType::Int64(value) => *value,
+            DataType::Int32(value) => *value as i64,
+            DataType::Timestamp(micros) => *micros,
+            _ => panic!("Cannot convert to 64-bit integer"),
+        }
+    }
+
     pub fn as_float(&self) -> f64 {
         match self {
             DataType::Float64(value) => *value,
@@ -65,6 +215,170 @@ impl DataType {
     }
 }
 
+// Zero value of the type with the given SQL name, the inverse of type_name.
+// Names are case insensitive and the usual aliases (INTEGER, TEXT, DOUBLE, BOOLEAN...) are accepted.
+pub fn datatype_from_name(name: &str) -> Option<DataType> {
+    match name.to_uppercase().as_str() {
+        "VARCHAR" | "TEXT" | "STRING" => Some(DataType::Varchar(String::new())),
+        "INT" | "INTEGER" | "INT32" => Some(DataType::Int32(0)),
+        "BIGINT" | "INT64" => Some(DataType::Int64(0)),
+        "DATE" => Some(DataType::Date(0)),
+        "TIMESTAMP" => Some(DataType::Timestamp(0)),
+        "UUID" => Some(DataType::Uuid([0; 16])),
+        "FLOAT" | "DOUBLE" | "FLOAT64" | "REAL" => Some(DataType::Float64(0.0)),
+        "DECIMAL" | "NUMERIC" => Some(DataType::Decimal { mantissa: 0, scale: 0 }),
+        "BOOL" | "BOOLEAN" => Some(DataType::Bool(false)),
+        "NULL" => Some(DataType::Null),
+        _ => None,
+    }
+}
+
+// Days since 1970-01-01 of a date of the proleptic Gregorian calendar
+// Based on the days_from_civil algorithm by Howard Hinnant
+fn days_from_civil(year: i32, month: u32, day: u32) -> i32 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = if year >= 0 { year } else { year - 399 } / 400;
+    let year_of_era = year - era * 400;
+    let month = month as i32;
+    let day_of_year = (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + day as i32 - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+// Same as days_from_civil, for dates that may not exist
+fn checked_days_from_civil(year: i32, month: u32, day: u32) -> Result<i32, String> {
+    if !(0..=MAX_YEAR).contains(&year) || !(1..=12).contains(&month) || day == 0 || day > days_in_month(year, month) {
+        return Err(format!("Invalid date: {:04}-{:02}-{:02}", year, month, day));
+    }
+    Ok(days_from_civil(year, month, day))
+}
+
+// Number of days of a month, February has 29 in leap years
+fn days_in_month(year: i32, month: u32) -> u32 {
+    match month {
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+// Inverse of days_from_civil: (year, month, day) of a number of days since 1970-01-01
+fn civil_from_days(days: i32) -> (i32, u32, u32) {
+    let days = days + 719468;
+    let era = if days >= 0 { days } else { days - 146096 } / 146097;
+    let day_of_era = days - era * 146097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * shifted_month + 2) / 5 + 1) as u32;
+    let month = if shifted_month < 10 { shifted_month + 3 } else { shifted_month - 9 } as u32;
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+// Values are displayed the way a result printer shows them: text without quotes and NULL as NULL
+impl std::fmt::Display for DataType {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        // pad honours width and alignment, so values line up in result tables
+        f.pad(&self.as_string())
+    }
+}
+
+// Arithmetic used to evaluate expressions such as price * 2.
+// Int32 op Int32 stays Int32, mixing in an Int64 widens to Int64 and mixing in a Float64 promotes to Float64.
+// Overflow, division by zero and non numeric operands are errors. A NULL operand gives NULL, as in SQL.
+impl DataType {
+    pub fn add(&self, other: &DataType) -> Result<DataType, String> {
+        self.arithmetic(other, "+", i32::checked_add, i64::checked_add, |a, b| a + b)
+    }
+
+    pub fn sub(&self, other: &DataType) -> Result<DataType, String> {
+        self.arithmetic(other, "-", i32::checked_sub, i64::checked_sub, |a, b| a - b)
+    }
+
+    pub fn mul(&self, other: &DataType) -> Result<DataType, String> {
+        self.arithmetic(other, "*", i32::checked_mul, i64::checked_mul, |a, b| a * b)
+    }
+
+    pub fn div(&self, other: &DataType) -> Result<DataType, String> {
+        if matches!(other, DataType::Int32(0) | DataType::Int64(0)) || *other == DataType::Float64(0.0) {
+            return Err("Division by zero".to_string());
+        }
+        self.arithmetic(other, "/", i32::checked_div, i64::checked_div, |a, b| a / b)
+    }
+
+    fn arithmetic(
+        &self,
+        other: &DataType,
+        op: &str,
+        int_op: fn(i32, i32) -> Option<i32>,
+        long_op: fn(i64, i64) -> Option<i64>,
+        float_op: fn(f64, f64) -> f64,
+    ) -> Result<DataType, String> {
+        use DataType::*;
+        let overflow = || format!("Overflow in {} {} {}", self, op, other);
+        match (self, other) {
+            (Null, _) | (_, Null) => Ok(Null),
+            (Int32(a), Int32(b)) => int_op(*a, *b).map(Int32).ok_or_else(overflow),
+            (Int32(_) | Int64(_), Int32(_) | Int64(_)) => long_op(self.as_i64(), other.as_i64()).map(Int64).ok_or_else(overflow),
+            (Int32(_) | Int64(_) | Float64(_), Int32(_) | Int64(_) | Float64(_)) => Ok(Float64(float_op(self.as_f64(), other.as_f64()))),
+            _ => Err(format!("Cannot apply {} to {} and {}", op, self.type_name(), other.type_name())),
+        }
+    }
+}
+
+// Conversions from plain Rust values, so values can be built with .into()
+impl From<i32> for DataType {
+    fn from(value: i32) -> Self {
+        DataType::Int32(value)
+    }
+}
+
+impl From<i64> for DataType {
+    fn from(value: i64) -> Self {
+        DataType::Int64(value)
+    }
+}
+
+impl From<f64> for DataType {
+    fn from(value: f64) -> Self {
+        DataType::Float64(value)
+    }
+}
+
+impl From<bool> for DataType {
+    fn from(value: bool) -> Self {
+        DataType::Bool(value)
+    }
+}
+
+impl From<String> for DataType {
+    fn from(value: String) -> Self {
+        DataType::Varchar(value)
+    }
+}
+
+impl From<&str> for DataType {
+    fn from(value: &str) -> Self {
+        DataType::Varchar(value.to_string())
+    }
+}
+
+// None becomes NULL
+impl<T: Into<DataType>> From<Option<T>> for DataType {
+    fn from(value: Option<T>) -> Self {
+        value.map_or(DataType::Null, Into::into)
+    }
+}
+
+// Equality follows the total order, so -0.0 equals 0.0 and NaN equals itself
+impl PartialEq for DataType {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
 impl Eq for DataType {}
 
 impl Hash for DataType {
@@ -73,13 +387,136 @@ impl Hash for DataType {
         match self {
             DataType::Varchar(value) => value.hash(state),
             DataType::Int32(value) => value.hash(state),
-            DataType::Float64(value) => value.to_bits().hash(state),
+            DataType::Int64(value) => value.hash(state),
+            DataType::Date(days) => days.hash(state),
+            DataType::Timestamp(micros) => micros.hash(state),
+            DataType::Uuid(bytes) => bytes.hash(state),
+            // -0.0 is equal to 0.0, so both must hash the same
+            DataType::Float64(value) => (if *value == 0.0 { 0.0f64 } else { *value }).to_bits().hash(state),
+            DataType::Decimal { mantissa, scale } => {
+                mantissa.hash(state);
+                scale.hash(state);
+            }
             DataType::Bool(value) => value.hash(state),
             DataType::Null => 0.hash(state),
         }
     }
 }
 
+// Values are ordered as follows:
+// - Null sorts lowest, before any other value.
+// - Values of the same type compare by value (strings lexicographically, floats by total_cmp).
+// - Int32, Int64, Float64 and Decimal compare numerically with each other; when numerically equal
+//   the narrower type sorts first, so the order stays consistent with equality.
+// - Otherwise values of different types are ordered by type: Bool < numbers < Date < Timestamp < Uuid < Varchar.
+impl Ord for DataType {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.value_cmp(other).then_with(|| match (self, other) {
+            (DataType::Decimal { scale: sa, .. }, DataType::Decimal { scale: sb, .. }) => sa.cmp(sb),
+            _ => self.type_rank().cmp(&other.type_rank()),
+        })
+    }
+}
+
+impl PartialOrd for DataType {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+// Compares two decimals by value.
+// Both mantissas are brought to the larger scale when that fits in an i128.
+// Otherwise the scales are too far apart, so the signs, the whole parts and the fraction digits are compared in turn.
+fn decimal_cmp(a: i64, sa: u8, b: i64, sb: u8) -> Ordering {
+    let scale = sa.max(sb) as u32;
+    let rescale = |mantissa: i64, from: u8| {
+        10i128.checked_pow(scale - from as u32).and_then(|factor| (mantissa as i128).checked_mul(factor))
+    };
+    if let (Some(a), Some(b)) = (rescale(a, sa), rescale(b, sb)) {
+        return a.cmp(&b);
+    }
+
+    let sign = a.signum().cmp(&b.signum());
+    if sign != Ordering::Equal {
+        return sign;
+    }
+    // Digits of the absolute value, with at least one before the point, as in as_string
+    let split = |mantissa: i64, scale: u8| {
+        let digits = format!("{:0width$}", mantissa.unsigned_abs(), width = scale as usize + 1);
+        let (whole, fraction) = digits.split_at(digits.len() - scale as usize);
+        (whole.trim_start_matches('0').to_string(), fraction.to_string())
+    };
+    let (whole_a, fraction_a) = split(a, sa);
+    let (whole_b, fraction_b) = split(b, sb);
+    // Trailing zeros do not change a fraction, so both are padded to the same length
+    let width = fraction_a.len().max(fraction_b.len());
+    let magnitude = whole_a.len().cmp(&whole_b.len())
+        .then_with(|| whole_a.cmp(&whole_b))
+        .then_with(|| format!("{:0<width$}", fraction_a).cmp(&format!("{:0<width$}", fraction_b)));
+    if a < 0 { magnitude.reverse() } else { magnitude }
+}
+
+impl DataType {
+    // Compares the values alone, so numbers of different types (or decimals of different scales)
+    // are Equal when they are numerically equal. Ord breaks those ties to stay consistent with ==.
+    fn value_cmp(&self, other: &Self) -> Ordering {
+        use DataType::*;
+        match (self, other) {
+            (Varchar(a), Varchar(b)) => a.cmp(b),
+            (Bool(a), Bool(b)) => a.cmp(b),
+            (Date(a), Date(b)) => a.cmp(b),
+            (Timestamp(a), Timestamp(b)) => a.cmp(b),
+            (Uuid(a), Uuid(b)) => a.cmp(b),
+            (Decimal { mantissa: a, scale: sa }, Decimal { mantissa: b, scale: sb }) => decimal_cmp(*a, *sa, *b, *sb),
+            (Int32(_) | Int64(_), Int32(_) | Int64(_)) => self.as_i64().cmp(&other.as_i64()),
+            (Int32(_) | Int64(_) | Float64(_) | Decimal { .. }, Int32(_) | Int64(_) | Float64(_) | Decimal { .. }) => self.as_f64().total_cmp(&other.as_f64()),
+            _ => self.type_rank().cmp(&other.type_rank()),
+        }
+    }
+
+    pub fn is_null(&self) -> bool {
+        matches!(self, DataType::Null)
+    }
+
+    // Equality with SQL semantics: comparing with NULL is unknown (None), even NULL = NULL.
+    // Numbers of different types are equal when their values are, e.g. 1 = 1.0.
+    pub fn sql_eq(&self, other: &DataType) -> Option<bool> {
+        if self.is_null() || other.is_null() {
+            return None;
+        }
+        Some(self.value_cmp(other) == Ordering::Equal)
+    }
+
+    // Position of the type in the cross-type ordering
+    fn type_rank(&self) -> u8 {
+        match self {
+            DataType::Null => 0,
+            DataType::Bool(_) => 1,
+            DataType::Int32(_) => 2,
+            DataType::Int64(_) => 3,
+            DataType::Float64(_) => 4,
+            DataType::Decimal { .. } => 5,
+            DataType::Date(_) => 6,
+            DataType::Timestamp(_) => 7,
+            DataType::Uuid(_) => 8,
+            DataType::Varchar(_) => 9,
+        }
+    }
+
+    // Numeric value as a float, only used for comparisons
+    fn as_f64(&self) -> f64 {
+        match self {
+            DataType::Int32(value) => *value as f64,
+            DataType::Int64(value) => *value as f64,
+            // total_cmp orders -0.0 before 0.0, so it is folded into 0.0
+            DataType::Float64(value) if *value == 0.0 => 0.0,
+            DataType::Float64(value) => *value,
+            DataType::Decimal { mantissa, scale } => *mantissa as f64 / 10f64.powi(*scale as i32),
+            _ => panic!("Cannot convert to float"),
+        }
+    }
+}
+
 // TRAIT SERIALIZABLE
 // THIS IS MY IMPLEMENTATION OF THE SERIALIZABLE TRAIT
 // I WILL USE THIS TRAIT TO SERIALIZE AND DESERIALIZE DATA TYPES
@@ -88,6 +525,20 @@ pub trait Serializable {
     fn serialize(&self) -> Vec<u8>;
     fn deserialize(buffer: &[u8], offset: &mut usize) -> Self where Self: Sized;
 
+    // Writes the serialized form into any sink, such as a file.
+    // Types that can stream their fields override this to skip the intermediate buffer.
+    fn serialize_into<W: Write>(&self, w: &mut W) -> io::Result<()> where Self: Sized {
+        w.write_all(&self.serialize())
+    }
+
+    fn serialize_list_into<T: Serializable, W: Write>(data: &[T], w: &mut W) -> io::Result<()> where Self: Sized {
+        DataType::Int32(data.len() as i32).serialize_into(w)?;
+        for item in data {
+            item.serialize_into(w)?;
+        }
+        Ok(())
+    }
+
     fn serialize_list<T: Serializable>(data: &[T]) -> Vec<u8> where Self: Sized {
         let list_len = DataType::Int32(data.len() as i32);
         let mut result = list_len.serialize();
@@ -150,72 +601,113 @@ pub trait Serializable {
         }
         result
     }
+
+    // Same layout as the hashmap, but the keys are written in order so the bytes are reproducible
+    fn serialize_btreemap<T: Serializable>(data: &std::collections::BTreeMap<DataType, T>) -> Vec<u8> where Self: Sized {
+        let list_len = DataType::Int32(data.len() as i32);
+        let mut result = list_len.serialize();
+        for (key, value) in data {
+            result.extend(key.serialize());
+            result.extend(value.serialize());
+        }
+        result
+    }
+
+    fn deserialize_btreemap(buffer: &[u8], offset: &mut usize) -> std::collections::BTreeMap<DataType, Self> where Self: Sized {
+        let len = DataType::deserialize(buffer, offset);
+        let mut result = std::collections::BTreeMap::new();
+        for _ in 0..len.as_int() {
+            let key = DataType::deserialize(buffer, offset);
+            let value = Self::deserialize(buffer, offset);
+            result.insert(key, value);
+        }
+        result
+    }
 }
 
 // Implementation of the Serializable trait for DataType
 impl Serializable for DataType {
     fn serialize(&self) -> Vec<u8> {
         let mut buffer = Vec::new();
+        self.serialize_into(&mut buffer).expect("writing to a Vec cannot fail");
+        buffer
+    }
 
+    // Writes the datatype straight into the sink, without building a buffer first
+    fn serialize_into<W: Write>(&self, w: &mut W) -> io::Result<()> {
         match self {
             DataType::Varchar(value) => {
-                buffer.push(0x01); // Type marker for VARCHAR
-                let len = value.len() as u8;
-                if len >  MAX_STR_SIZE as u8 {
-                    panic!("String length exceeds maximum length");
-                }
-                
-                // String length (1 byte)
-                buffer.push(len);
-                // Convert the string to bytes and add a padding
-                let mut padded_value = value.as_bytes().to_vec();
-                let padding_size = MAX_STR_SIZE - len as usize;
-                if padding_size > 0 {
-                        padded_value.extend(vec![0u8; padding_size]); // Add zeros for padding
-                }
+                w.write_all(&[0x01])?; // Type marker for VARCHAR
+                let bytes = value.as_bytes();
+                let len = bytes.len() as u32;
+                let len_bytes = len.to_le_bytes();
 
-                // The underlying buffer is extended with the bytes of the string
-                buffer.extend(padded_value);
-                         
-                
+                // String length (4 bytes) followed by exactly that many bytes, no padding
+                w.write_all(&len_bytes)?;
+                w.write_all(bytes)?;
             }
 
             DataType::Int32(value) => {
-                buffer.push(0x02); // Type marker for INT32
-                let bytes = if ENDIANESS {
-                    value.to_le_bytes()
-                } else {
-                    value.to_be_bytes()
-                };
+                w.write_all(&[0x02])?; // Type marker for INT32
+                let bytes = value.to_le_bytes();
                 // Serialization of INT32 (4 bytes)
-                buffer.extend(&bytes);
+                w.write_all(&bytes)?;
+            }
+
+            DataType::Int64(value) => {
+                w.write_all(&[0x05])?; // Type marker for INT64
+                let bytes = value.to_le_bytes();
+                // Serialization of INT64 (8 bytes)
+                w.write_all(&bytes)?;
+            }
+
+            DataType::Date(days) => {
+                w.write_all(&[0x06])?; // Type marker for DATE
+                let bytes = days.to_le_bytes();
+                // Serialization of DATE (4 bytes)
+                w.write_all(&bytes)?;
             }
 
             DataType::Float64(value) => {
-                buffer.push(0x03); // Type marker for FLOAT64
-                let bytes = if ENDIANESS {
-                    value.to_le_bytes()
-                } else {
-                    value.to_be_bytes()
-                };
+                w.write_all(&[0x03])?; // Type marker for FLOAT64
+                let bytes = value.to_le_bytes();
                 // Serialization of FLOAT64 (8 bytes)
-                buffer.extend(&bytes);
+                w.write_all(&bytes)?;
+            }
+
+            DataType::Timestamp(micros) => {
+                w.write_all(&[0x08])?; // Type marker for TIMESTAMP
+                // Serialization of TIMESTAMP (8 bytes)
+                w.write_all(&micros.to_le_bytes())?;
+            }
+
+            DataType::Uuid(bytes) => {
+                w.write_all(&[0x09])?; // Type marker for UUID
+                // Serialization of UUID (16 bytes)
+                w.write_all(bytes)?;
+            }
+
+            DataType::Decimal { mantissa, scale } => {
+                w.write_all(&[0x07])?; // Type marker for DECIMAL
+                // Serialization of the mantissa (8 bytes) followed by the scale (1 byte)
+                w.write_all(&mantissa.to_le_bytes())?;
+                w.write_all(&[*scale])?;
             }
 
             DataType::Bool(value) => {
-                buffer.push(0x04);  // Type marker for BOOL
+                w.write_all(&[0x04])?;  // Type marker for BOOL
                 // Serialization of BOOL (1 byte)
-                buffer.push(*value as u8);
+                w.write_all(&[*value as u8])?;
             }
 
             DataType::Null => {
-                buffer.push(0x00); // Type marker for NULL
+                w.write_all(&[0x00])?; // Type marker for NULL
                 // Serialization of NULL (1 byte)
-                buffer.push(0u8);  // NULL is represented as a bitmap of 1 byte
+                w.write_all(&[0u8])?;  // NULL is represented as a bitmap of 1 byte
             }
         }
 
-        buffer
+        Ok(())
     }
 
 
@@ -223,53 +715,106 @@ impl Serializable for DataType {
     // The function reads the type marker and then deserializes the data accordingly
     // the offset is updated to point to the next byte after the deserialized data
     fn deserialize(buffer: &[u8], offset: &mut usize) -> Self where Self: Sized {
-        let data_type = buffer[*offset];
-        *offset += 1;
+        DataType::try_deserialize(buffer, offset).expect("failed to deserialize a datatype")
+    }
+}
+
 
-        match data_type {
+// Error returned when a buffer does not hold a valid datatype
+#[derive(Debug, Clone, PartialEq)]
+pub enum DeserializeError {
+    Truncated { expected: usize, available: usize }, // Not enough bytes left in the buffer
+    InvalidUtf8,                                      // Varchar bytes that are not valid UTF-8
+}
+
+impl std::fmt::Display for DeserializeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            DeserializeError::Truncated { expected, available } => {
+                write!(f, "buffer truncated: expected {} bytes, {} available", expected, available)
+            }
+            DeserializeError::InvalidUtf8 => write!(f, "varchar is not valid UTF-8"),
+        }
+    }
+}
+
+// Takes the next n bytes of the buffer, checking first that they are there
+fn take<'a>(buffer: &'a [u8], offset: &mut usize, n: usize) -> Result<&'a [u8], DeserializeError> {
+    let available = buffer.len().saturating_sub(*offset);
+    if available < n {
+        return Err(DeserializeError::Truncated { expected: n, available });
+    }
+    let bytes = &buffer[*offset..*offset + n];
+    *offset += n;
+    Ok(bytes)
+}
+
+impl DataType {
+    // Bounds-checked version of deserialize.
+    // On error the offset is left where it was.
+    pub fn try_deserialize(buffer: &[u8], offset: &mut usize) -> Result<DataType, DeserializeError> {
+        let mut pos = *offset;
+        let data_type = take(buffer, &mut pos, 1)?[0];
+
+        let value = match data_type {
             0x01 => { // Varchar
-                let len = buffer[*offset] as usize;
-                *offset += 1;
-                let value = String::from_utf8(buffer[*offset..*offset + len].to_vec()).unwrap();
-                *offset += MAX_STR_SIZE;
+                let len = u32::from_le_bytes(take(buffer, &mut pos, STR_LEN_SIZE)?.try_into().unwrap()) as usize;
+                // The length is in bytes, so the slice always ends on a codepoint boundary
+                let bytes = take(buffer, &mut pos, len)?;
+                let value = String::from_utf8(bytes.to_vec()).map_err(|_| DeserializeError::InvalidUtf8)?;
                 DataType::Varchar(value)
             }
 
             0x02 => { // INT32
-                let mut bytes = [0u8; MAX_INT4_SIZE];
-                bytes.copy_from_slice(&buffer[*offset..*offset + MAX_INT4_SIZE]);
-                *offset += MAX_INT4_SIZE;
-                let value = if ENDIANESS {
-                    i32::from_le_bytes(bytes)
-                } else {
-                    i32::from_be_bytes(bytes)
-                };
-                DataType::Int32(value)
+                let bytes = take(buffer, &mut pos, MAX_INT4_SIZE)?;
+                DataType::Int32(i32::from_le_bytes(bytes.try_into().unwrap()))
             }
 
             0x03 => { // FLOAT64
-                let mut bytes = [0u8; MAX_FLOAT_SIZE];
-                bytes.copy_from_slice(&buffer[*offset..*offset + MAX_FLOAT_SIZE]);
-                *offset += MAX_FLOAT_SIZE;
-                let value = if ENDIANESS {
-                    f64::from_le_bytes(bytes)
-                } else {
-                    f64::from_be_bytes(bytes)
-                };
-                DataType::Float64(value)
+                let bytes = take(buffer, &mut pos, MAX_FLOAT_SIZE)?;
+                DataType::Float64(f64::from_le_bytes(bytes.try_into().unwrap()))
+            }
+
+            0x05 => { // INT64
+                let bytes = take(buffer, &mut pos, MAX_INT8_SIZE)?;
+                DataType::Int64(i64::from_le_bytes(bytes.try_into().unwrap()))
+            }
+
+            0x06 => { // DATE
+                let bytes = take(buffer, &mut pos, MAX_INT4_SIZE)?;
+                DataType::Date(i32::from_le_bytes(bytes.try_into().unwrap()))
+            }
+
+            0x08 => { // TIMESTAMP
+                let bytes = take(buffer, &mut pos, MAX_INT8_SIZE)?;
+                DataType::Timestamp(i64::from_le_bytes(bytes.try_into().unwrap()))
+            }
+
+            0x09 => { // UUID
+                let bytes = take(buffer, &mut pos, UUID_SIZE)?;
+                DataType::Uuid(bytes.try_into().unwrap())
+            }
+
+            0x07 => { // DECIMAL
+                let bytes = take(buffer, &mut pos, MAX_INT8_SIZE)?;
+                let mantissa = i64::from_le_bytes(bytes.try_into().unwrap());
+                let scale = take(buffer, &mut pos, 1)?[0];
+                DataType::Decimal { mantissa, scale }
             }
 
             0x04 => { // BOOL
-                let value = buffer[*offset] != 0;
-                *offset += BOOLEAN_SIZE;
-                DataType::Bool(value)
+                let bytes = take(buffer, &mut pos, BOOLEAN_SIZE)?;
+                DataType::Bool(bytes[0] != 0)
             }
 
-            _ => { 
-                *offset += 1;
-                DataType::Null 
+            _ => {
+                take(buffer, &mut pos, 1)?;
+                DataType::Null
             }
-        }
+        };
+
+        *offset = pos;
+        Ok(value)
     }
 }
 
@@ -290,6 +835,7 @@ mod tests {
         let tests = vec![
             Varchar("Hello".to_string()),
             Int32(42),
+            Int64(1 << 40),
             Float64(4.4849),
             Bool(true),
             Null,
@@ -341,7 +887,10 @@ mod tests {
         let float_min = Float64(f64::MIN);
         let float_max = Float64(f64::MAX);
 
-        for test in [int_min, int_max, float_min, float_max] {
+        let bigint_min = Int64(i64::MIN);
+        let bigint_max = Int64(i64::MAX);
+
+        for test in [int_min, int_max, float_min, float_max, bigint_min, bigint_max] {
             let serialized = test.serialize();
             let mut offset = 0;
             let deserialized = DataType::deserialize(&serialized, &mut offset);
@@ -349,4 +898,357 @@ mod tests {
             assert_eq_data(test.clone(), deserialized);
         }
     }
-}
\ No newline at end of file
+
+    // Test that varchars are stored with their exact length and no padding
+    #[test]
+    fn test_varchar_variable_length() {
+        use DataType::*;
+        let long = "a".repeat(200);
+        for value in ["", long.as_str(), "añoñ über €"] {
+            let data = Varchar(value.to_string());
+            let serialized = data.serialize();
+            assert_eq!(serialized.len(), 1 + STR_LEN_SIZE + value.len());
+
+            let mut offset = 0;
+            let deserialized = DataType::deserialize(&serialized, &mut offset);
+            assert_eq!(offset, serialized.len());
+            assert_eq_data(data, deserialized);
+        }
+    }
+
+    // Test that a btreemap serializes to the same bytes every time and reads back in order
+    #[test]
+    fn test_btreemap() {
+        use std::collections::BTreeMap;
+        use DataType::*;
+        let mut map = BTreeMap::new();
+        for (key, value) in [("zeta", 1), ("alpha", 2), ("mid", 3)] {
+            map.insert(Varchar(key.to_string()), Int32(value));
+        }
+        let serialized = DataType::serialize_btreemap(&map);
+        assert_eq!(serialized, DataType::serialize_btreemap(&map.clone()));
+
+        let mut offset = 0;
+        let deserialized = DataType::deserialize_btreemap(&serialized, &mut offset);
+        assert_eq!(deserialized, map);
+        assert_eq!(deserialized.keys().next(), Some(&Varchar("alpha".to_string())));
+    }
+
+    // Test that uuids round trip and parse from their canonical form
+    #[test]
+    fn test_uuid() {
+        use DataType::*;
+        let value = Uuid([0x3f, 0x9a, 0x01, 0xc4, 0x7e, 0x22, 0xd8, 0x5b, 0x90, 0x11, 0xee, 0x6d, 0x4a, 0xb7, 0x02, 0xf3]);
+        let serialized = value.serialize();
+        assert_eq!(serialized.len(), 1 + UUID_SIZE);
+        let mut offset = 0;
+        assert_eq!(DataType::deserialize(&serialized, &mut offset), value);
+        assert_eq!(value.as_string(), "3f9a01c4-7e22-d85b-9011-ee6d4ab702f3");
+
+        let parsed = DataType::uuid("123e4567-e89b-12d3-a456-426614174000").unwrap();
+        assert_eq!(parsed, Uuid([0x12, 0x3e, 0x45, 0x67, 0xe8, 0x9b, 0x12, 0xd3, 0xa4, 0x56, 0x42, 0x66, 0x14, 0x17, 0x40, 0x00]));
+        assert_eq!(parsed.as_string(), "123e4567-e89b-12d3-a456-426614174000");
+        assert!(DataType::uuid("123e4567e89b12d3a456426614174000").is_err());
+        assert!(DataType::uuid("123e4567-e89b-12d3-a456-42661417400g").is_err());
+    }
+
+    // Test that timestamps keep microsecond precision and format as ISO-8601
+    #[test]
+    fn test_timestamp() {
+        use DataType::*;
+        assert_eq!(Timestamp(0).as_string(), "1970-01-01T00:00:00.000000");
+        assert_eq!(Timestamp(-1).as_string(), "1969-12-31T23:59:59.999999");
+
+        let event = DataType::timestamp(2024, 2, 29, 13, 45, 30, 123456);
+        assert_eq!(event, Timestamp(1709214330123456));
+        assert_eq!(event.as_string(), "2024-02-29T13:45:30.123456");
+        assert_eq!(event.to_date(), DataType::date(2024, 2, 29));
+        assert_eq!(DataType::timestamp_from_date(19782), DataType::timestamp(2024, 2, 29, 0, 0, 0, 0));
+
+        let serialized = event.serialize();
+        assert_eq!(serialized.len(), 1 + MAX_INT8_SIZE);
+        let mut offset = 0;
+        assert_eq!(DataType::deserialize(&serialized, &mut offset), event);
+    }
+
+    // Test that decimals whose scales are far apart compare without overflowing
+    #[test]
+    fn test_decimal_cmp_large_scales() {
+        use DataType::*;
+        // 10^60 does not fit in an i128, so these take the digit by digit comparison
+        let tiny = Decimal { mantissa: 5, scale: 60 };
+        let big = Decimal { mantissa: i64::MAX, scale: 0 };
+        assert!(tiny < Decimal { mantissa: 1, scale: 0 });
+        assert!(tiny > Decimal { mantissa: 0, scale: 0 });
+        assert!(tiny < big);
+        assert!(Decimal { mantissa: -5, scale: 60 } > Decimal { mantissa: -1, scale: 0 });
+        assert!(Decimal { mantissa: -5, scale: 60 } < Decimal { mantissa: 0, scale: 0 });
+        assert!(Decimal { mantissa: 7, scale: 60 } > tiny);
+        // i64::MAX at scale 40 scaled up would overflow the i128 as well
+        let wide = Decimal { mantissa: i64::MAX, scale: 40 };
+        assert!(wide < Decimal { mantissa: 1, scale: 0 });
+        assert!(wide > Decimal { mantissa: 9, scale: 22 });
+        assert_eq!(Decimal { mantissa: 50, scale: 61 }.sql_eq(&tiny), Some(true));
+        assert_eq!(Decimal { mantissa: 5, scale: 0 }.cmp(&Decimal { mantissa: 5, scale: 60 }), std::cmp::Ordering::Greater);
+    }
+
+    // Test that decimals are parsed, formatted and serialized without losing digits
+    #[test]
+    fn test_decimal() {
+        use DataType::*;
+        let price = DataType::decimal("12.34").unwrap();
+        assert_eq!(price, Decimal { mantissa: 1234, scale: 2 });
+        assert_eq!(price.as_string(), "12.34");
+
+        let serialized = price.serialize();
+        let mut offset = 0;
+        assert_eq!(DataType::deserialize(&serialized, &mut offset), price);
+        assert_eq!(offset, serialized.len());
+
+        assert_eq!(DataType::decimal("-0.05").unwrap().as_string(), "-0.05");
+        assert_eq!(DataType::decimal("7").unwrap(), Decimal { mantissa: 7, scale: 0 });
+        assert_eq!(Decimal { mantissa: 5, scale: 3 }.as_string(), "0.005");
+        assert!(DataType::decimal("1.2.3").is_err());
+        assert!(DataType::decimal("").is_err());
+
+        assert!(DataType::decimal("1.25").unwrap() < DataType::decimal("1.5").unwrap());
+        assert!(Int32(1) < DataType::decimal("1.5").unwrap());
+    }
+
+    // Test arithmetic between numeric values
+    #[test]
+    fn test_arithmetic() {
+        use DataType::*;
+        assert_eq!(Int32(2).add(&Int32(3)), Ok(Int32(5)));
+        assert_eq!(Int32(2).sub(&Int32(3)), Ok(Int32(-1)));
+        assert_eq!(Int32(4).mul(&Int32(3)), Ok(Int32(12)));
+        assert_eq!(Int32(7).div(&Int32(2)), Ok(Int32(3)));
+        assert_eq!(Int32(2).mul(&Float64(1.5)), Ok(Float64(3.0)));
+        assert_eq!(Float64(1.0).div(&Int32(4)), Ok(Float64(0.25)));
+        assert_eq!(Int32(1).add(&Int64(2)), Ok(Int64(3)));
+        assert_eq!(Int32(1).add(&Null), Ok(Null));
+
+        assert!(Int32(1).div(&Int32(0)).is_err());
+        assert!(Float64(1.0).div(&Float64(0.0)).is_err());
+        assert!(Int32(i32::MAX).add(&Int32(1)).is_err());
+        assert!(Int32(1).add(&Varchar("a".to_string())).is_err());
+        assert!(Bool(true).mul(&Int32(2)).is_err());
+    }
+
+    // Test type names in both directions
+    #[test]
+    fn test_type_names() {
+        use DataType::*;
+        assert_eq!(Int32(30).type_name(), "INT");
+        assert_eq!(Varchar("a".to_string()).type_name(), "VARCHAR");
+        assert_eq!(Float64(1.5).type_name(), "FLOAT");
+        assert_eq!(Bool(true).type_name(), "BOOL");
+        assert_eq!(Null.type_name(), "NULL");
+
+        assert_eq!(datatype_from_name("INT"), Some(Int32(0)));
+        assert_eq!(datatype_from_name("integer"), Some(Int32(0)));
+        assert_eq!(datatype_from_name("VARCHAR"), Some(Varchar(String::new())));
+        assert_eq!(datatype_from_name("Boolean"), Some(Bool(false)));
+        assert_eq!(datatype_from_name("BLOB"), None);
+        assert_eq!(datatype_from_name(""), None);
+
+        // Every type name maps back to a value of the same type
+        let values = [Varchar("a".to_string()), Int32(1), Int64(1), Date(1), Timestamp(1), Uuid([1; 16]),
+            Float64(1.0), Decimal { mantissa: 1, scale: 1 }, Bool(true), Null];
+        for value in values {
+            let zero = datatype_from_name(value.type_name()).unwrap();
+            assert_eq!(zero.get_type(), value.get_type());
+        }
+    }
+
+    // Test how each variant is displayed
+    #[test]
+    fn test_display() {
+        use DataType::*;
+        assert_eq!(Varchar("John".to_string()).to_string(), "John");
+        assert_eq!(Int32(30).to_string(), "30");
+        assert_eq!(Int64(-5).to_string(), "-5");
+        assert_eq!(Float64(10.5).to_string(), "10.5");
+        assert_eq!(Bool(true).to_string(), "true");
+        assert_eq!(Bool(false).to_string(), "false");
+        assert_eq!(Null.to_string(), "NULL");
+        assert_eq!(DataType::date(2024, 1, 31).to_string(), "2024-01-31");
+        assert_eq!(Timestamp(0).to_string(), "1970-01-01T00:00:00.000000");
+        assert_eq!(Decimal { mantissa: 1234, scale: 2 }.to_string(), "12.34");
+        assert_eq!(Uuid([0; 16]).to_string(), "00000000-0000-0000-0000-000000000000");
+        assert_eq!(format!("[{:>4}]", Int32(7)), "[   7]");
+    }
+
+    // Test the conversions from plain Rust values
+    #[test]
+    fn test_from_conversions() {
+        use DataType::*;
+        let int: DataType = 42.into();
+        assert_eq!(int, Int32(42));
+        assert_eq!(DataType::from(7i64), Int64(7));
+        assert_eq!(DataType::from(1.5), Float64(1.5));
+        assert_eq!(DataType::from(true), Bool(true));
+        assert_eq!(DataType::from("text".to_string()), Varchar("text".to_string()));
+        assert_eq!(DataType::from("text"), Varchar("text".to_string()));
+        assert_eq!(DataType::from(Some(3)), Int32(3));
+        assert_eq!(DataType::from(Some("x")), Varchar("x".to_string()));
+        assert_eq!(DataType::from(None::<i32>), Null);
+    }
+
+    // Test the ordering of values of the same and of different types
+    #[test]
+    fn test_ordering() {
+        use DataType::*;
+        assert!(Int32(1) < Int32(2));
+        assert!(Varchar("a".to_string()) < Varchar("b".to_string()));
+        assert!(Null < Int32(i32::MIN));
+        assert!(Null < Varchar("".to_string()));
+        assert!(Int32(5) > Null);
+        assert!(Int32(2) < Int64(3));
+        assert!(Int64(3) < Float64(3.5));
+        assert!(Int32(3) < Int64(3));
+        assert!(Float64(-1.0) < Int32(0));
+        assert!(DataType::date(2020, 1, 1) < DataType::date(2021, 1, 1));
+        assert!(Bool(true) < Int32(0));
+
+        let mut values = vec![Varchar("b".to_string()), Null, Int32(7), Float64(1.5), Varchar("a".to_string())];
+        values.sort();
+        assert_eq!(values, vec![Null, Float64(1.5), Int32(7), Varchar("a".to_string()), Varchar("b".to_string())]);
+    }
+
+    // Test that equality, ordering and hashing agree on signed zeros and NaN
+    #[test]
+    fn test_float_eq_consistent_with_ord() {
+        use std::collections::HashSet;
+        use DataType::*;
+        assert_eq!(Float64(-0.0), Float64(0.0));
+        assert_eq!(Float64(-0.0).cmp(&Float64(0.0)), Ordering::Equal);
+        assert_eq!(Float64(-0.0).cmp(&Int32(0)), Float64(0.0).cmp(&Int32(0)));
+        assert_eq!(Float64(f64::NAN), Float64(f64::NAN));
+        assert_ne!(Float64(0.0), Int32(0));
+
+        let set: HashSet<DataType> = [Float64(0.0), Float64(-0.0), Float64(f64::NAN), Float64(f64::NAN)].into_iter().collect();
+        assert_eq!(set.len(), 2);
+    }
+
+    // Test that short buffers return an error instead of panicking
+    #[test]
+    fn test_try_deserialize_truncated() {
+        use DataType::*;
+        let mut offset = 0;
+        assert_eq!(
+            DataType::try_deserialize(&[0x02], &mut offset),
+            Err(DeserializeError::Truncated { expected: 4, available: 0 })
+        );
+        assert_eq!(offset, 0);
+
+        let serialized = Int32(42).serialize();
+        let result = DataType::try_deserialize(&serialized[..3], &mut offset);
+        assert_eq!(result, Err(DeserializeError::Truncated { expected: 4, available: 2 }));
+        assert_eq!(offset, 0);
+
+        assert!(DataType::try_deserialize(&[], &mut offset).is_err());
+        assert_eq!(DataType::try_deserialize(&serialized, &mut offset), Ok(Int32(42)));
+        assert_eq!(offset, serialized.len());
+    }
+
+    // Test that integers are always written little-endian, whatever the host
+    #[test]
+    fn test_little_endian_layout() {
+        use DataType::*;
+        assert_eq!(Int32(1).serialize(), vec![0x02, 0x01, 0x00, 0x00, 0x00]);
+        assert_eq!(Int64(0x0102).serialize(), vec![0x05, 0x02, 0x01, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(Varchar("a".to_string()).serialize(), vec![0x01, 0x01, 0x00, 0x00, 0x00, b'a']);
+    }
+
+    // Test that multibyte strings keep their byte length and decode whole
+    #[test]
+    fn test_varchar_multibyte() {
+        use DataType::*;
+        // Short in characters but longer than 32 bytes
+        let value = "café àéîõü ñ 🦀🦀🦀🦀 😀".to_string();
+        assert!(value.chars().count() < 32 && value.len() > 32);
+
+        let data = Varchar(value.clone());
+        let serialized = data.serialize();
+        assert_eq!(serialized.len(), 1 + STR_LEN_SIZE + value.len());
+
+        let mut offset = 0;
+        let deserialized = DataType::deserialize(&serialized, &mut offset);
+        assert_eq!(offset, serialized.len());
+        assert_eq!(deserialized.as_string(), value);
+    }
+
+    // Test that dates are stored as days since the epoch and formatted back
+    #[test]
+    fn test_date() {
+        use DataType::*;
+        assert_eq!(DataType::date(1970, 1, 1), Date(0));
+        assert_eq!(DataType::date(2000, 3, 1), Date(11017));
+        assert_eq!(DataType::date(1969, 12, 31), Date(-1));
+
+        let date = DataType::date(2024, 2, 29);
+        let serialized = date.serialize();
+        assert_eq!(serialized.len(), 1 + MAX_INT4_SIZE);
+        let deserialized = DataType::deserialize(&serialized, &mut 0);
+        assert_eq_data(date, deserialized.clone());
+        assert_eq!(deserialized.as_string(), "2024-02-29");
+        assert_eq!(Date(-1).as_string(), "1969-12-31");
+    }
+
+    // Test that the checked constructors reject dates and times that do not exist
+    #[test]
+    fn test_try_date() {
+        assert_eq!(DataType::try_date(2024, 2, 29), Ok(DataType::date(2024, 2, 29)));
+        assert!(DataType::try_date(2000, 2, 29).is_ok());
+        assert!(DataType::try_date(2024, 12, 31).is_ok());
+        for (year, month, day) in [(2024, 13, 1), (2024, 0, 10), (2024, 2, 0), (2024, 2, 30), (2023, 2, 29), (1900, 2, 29), (2024, 4, 31), (10000, 1, 1), (-1, 1, 1), (i32::MAX, 1, 1)] {
+            assert!(DataType::try_date(year, month, day).is_err(), "{}-{}-{}", year, month, day);
+        }
+        assert_eq!(DataType::try_date(2024, 13, 45), Err("Invalid date: 2024-13-45".to_string()));
+
+        assert!(DataType::try_timestamp(2024, 2, 29, 23, 59, 59, 999_999).is_ok());
+        assert!(DataType::try_timestamp(2024, 2, 30, 0, 0, 0, 0).is_err());
+        assert!(DataType::try_timestamp(2024, 2, 29, 24, 0, 0, 0).is_err());
+        assert!(DataType::try_timestamp(2024, 2, 29, 0, 60, 0, 0).is_err());
+        assert!(DataType::try_timestamp(2024, 2, 29, 0, 0, 0, 1_000_000).is_err());
+        // Would overflow the seconds of the day if it was not rejected first
+        assert!(DataType::try_timestamp(2024, 2, 29, u32::MAX, 0, 0, 0).is_err());
+    }
+
+    #[test]
+    #[should_panic(expected = "Invalid date: 2024-13-01")]
+    fn test_date_panics_on_invalid_month() {
+        DataType::date(2024, 13, 1);
+    }
+
+    // NULL compared with anything, even NULL, is unknown
+    #[test]
+    fn test_sql_eq() {
+        use DataType::*;
+        assert!(Null.is_null());
+        assert!(!Int32(0).is_null());
+        assert_eq!(Null.sql_eq(&Null), None);
+        assert_eq!(Null.sql_eq(&Int32(1)), None);
+        assert_eq!(Varchar("a".to_string()).sql_eq(&Null), None);
+        assert_eq!(Int32(1).sql_eq(&Int32(1)), Some(true));
+        assert_eq!(Int32(1).sql_eq(&Int32(2)), Some(false));
+        assert_eq!(Int32(1).sql_eq(&Varchar("1".to_string())), Some(false));
+        // Numbers compare by value across types and scales
+        assert_eq!(Int32(1).sql_eq(&Int64(1)), Some(true));
+        assert_eq!(Int32(1).sql_eq(&Float64(1.0)), Some(true));
+        assert_eq!(Decimal { mantissa: 10, scale: 1 }.sql_eq(&Decimal { mantissa: 100, scale: 2 }), Some(true));
+    }
+
+    // Int64 keeps its own type while as_int only accepts Int32
+    #[test]
+    fn test_int64_accessors() {
+        use DataType::*;
+        assert_eq!(Int64(i64::MAX).as_i64(), i64::MAX);
+        assert_eq!(Int32(7).as_i64(), 7);
+        assert_eq!(Int32(7).as_int(), 7);
+        assert_eq!(Int64(-5).as_string(), "-5");
+        assert_eq!(Int64(1).get_type(), 0x05);
+        assert_ne!(Int64(1), Int32(1));
+    }
+}
@@ -0,0 +1,170 @@
+// mod wal;
+// src/wal.rs
+// The write-ahead log records every operation before it is applied to a page.
+// Records are appended to the end of the log file, so after a crash they can be replayed in order.
+// Nothing writes to the log yet: the insert path that will call log_insert does not exist, so the module is allowed to be unused until then.
+#![allow(dead_code)]
+
+use std::io::{Error, ErrorKind, Result};
+
+use crate::page::PageId;
+use crate::storagemanager::fileops::{ManagedFile, SmallFile};
+use crate::storagemanager::serialization::{DataType, DeserializeError, Serializable};
+
+
+const INSERT_OP: i32 = 1; // OPERATION TYPE OF AN INSERT RECORD
+
+
+// A single logged operation
+#[derive(Debug, PartialEq)]
+enum WalRecord {
+    Insert { page_id: PageId, tuple: Vec<DataType> },
+}
+
+// Each record is serialized as its operation type followed by its fields
+impl Serializable for WalRecord {
+    fn serialize(&self) -> Vec<u8> {
+        let mut serialized = Vec::new();
+        match self {
+            WalRecord::Insert { page_id, tuple } => {
+                serialized.extend(DataType::Int32(INSERT_OP).serialize());
+                serialized.extend(page_id.serialize());
+                serialized.extend(DataType::serialize_list(tuple));
+            }
+        }
+        serialized
+    }
+
+    fn deserialize(buffer: &[u8], offset: &mut usize) -> Self {
+        WalRecord::try_deserialize(buffer, offset).expect("failed to deserialize a WAL record")
+    }
+}
+
+// Reads a value, reporting a buffer that ends too soon as UnexpectedEof and any other problem as InvalidData
+fn read_value(buffer: &[u8], offset: &mut usize) -> Result<DataType> {
+    DataType::try_deserialize(buffer, offset).map_err(|error| match error {
+        DeserializeError::Truncated { .. } => Error::new(ErrorKind::UnexpectedEof, error.to_string()),
+        _ => Error::new(ErrorKind::InvalidData, error.to_string()),
+    })
+}
+
+impl WalRecord {
+    // Bounds-checked version of deserialize.
+    // A record cut short by the end of the buffer is an UnexpectedEof error, and an unknown operation is InvalidData.
+    // On error the offset is left where it was.
+    fn try_deserialize(buffer: &[u8], offset: &mut usize) -> Result<Self> {
+        let mut pos = *offset;
+        let record = match read_value(buffer, &mut pos)? {
+            DataType::Int32(INSERT_OP) => {
+                let page_id = read_value(buffer, &mut pos)?;
+                let len = match read_value(buffer, &mut pos)? {
+                    DataType::Int32(len) if len >= 0 => len,
+                    other => return Err(Error::new(ErrorKind::InvalidData, format!("Invalid tuple length in WAL record: {:?}", other))),
+                };
+                let mut tuple = Vec::new();
+                for _ in 0..len {
+                    tuple.push(read_value(buffer, &mut pos)?);
+                }
+                WalRecord::Insert { page_id, tuple }
+            }
+            op => return Err(Error::new(ErrorKind::InvalidData, format!("Unknown WAL operation: {:?}", op))),
+        };
+        *offset = pos;
+        Ok(record)
+    }
+}
+
+
+struct Wal {
+    file: ManagedFile,
+}
+
+impl Wal {
+    fn new(path: &str) -> Self {
+        Wal { file: ManagedFile::new(path) }
+    }
+
+    // Appends an insert record to the log
+    fn log_insert(&self, page_id: PageId, tuple: &[DataType]) -> Result<()> {
+        let record = WalRecord::Insert { page_id, tuple: tuple.to_vec() };
+        self.file.append_all(&record.serialize())
+    }
+
+    // Reads every record back, in the order they were logged.
+    // A log that was never written has no records.
+    // A crash while appending can leave a partial last record. That insert was never applied,
+    // so it is dropped and the complete records before it are returned.
+    fn replay(&self) -> Result<Vec<WalRecord>> {
+        let buffer = match self.file.read_to_end() {
+            Ok(buffer) => buffer,
+            Err(error) if error.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(error) => return Err(error),
+        };
+        let mut offset = 0;
+        let mut records = Vec::new();
+        while offset < buffer.len() {
+            match WalRecord::try_deserialize(&buffer, &mut offset) {
+                Ok(record) => records.push(record),
+                Err(error) if error.kind() == ErrorKind::UnexpectedEof => break,
+                Err(error) => return Err(error),
+            }
+        }
+        Ok(records)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_log_and_replay() {
+        let path = std::env::temp_dir().join("rustgresql_test_wal.log");
+        let _ = std::fs::remove_file(&path);
+        let wal = Wal::new(path.to_str().unwrap());
+        assert!(wal.replay().unwrap().is_empty());
+
+        let first = vec![DataType::Int32(1), DataType::Varchar("John".to_string())];
+        let second = vec![DataType::Int32(2), DataType::Null];
+        wal.log_insert(DataType::Int32(3), &first).unwrap();
+        wal.log_insert(DataType::Int32(7), &second).unwrap();
+
+        assert_eq!(wal.replay().unwrap(), vec![
+            WalRecord::Insert { page_id: DataType::Int32(3), tuple: first },
+            WalRecord::Insert { page_id: DataType::Int32(7), tuple: second },
+        ]);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_replay_truncated_record() {
+        let path = std::env::temp_dir().join("rustgresql_test_wal_truncated.log");
+        let _ = std::fs::remove_file(&path);
+        let wal = Wal::new(path.to_str().unwrap());
+        let first = vec![DataType::Int32(1), DataType::Varchar("John".to_string())];
+        wal.log_insert(DataType::Int32(3), &first).unwrap();
+        wal.log_insert(DataType::Int32(7), &[DataType::Int32(2), DataType::Varchar("Jane".to_string())]).unwrap();
+
+        // Cut the last record short, as a crash in the middle of the append would
+        let bytes = std::fs::read(&path).unwrap();
+        for cut in [1, 3, 8] {
+            std::fs::write(&path, &bytes[..bytes.len() - cut]).unwrap();
+            assert_eq!(wal.replay().unwrap(), vec![WalRecord::Insert { page_id: DataType::Int32(3), tuple: first.clone() }]);
+        }
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_replay_unknown_operation() {
+        let path = std::env::temp_dir().join("rustgresql_test_wal_unknown.log");
+        let _ = std::fs::remove_file(&path);
+        let wal = Wal::new(path.to_str().unwrap());
+        wal.log_insert(DataType::Int32(3), &[DataType::Int32(1)]).unwrap();
+        wal.file.append_all(&DataType::Int32(99).serialize()).unwrap();
+
+        let error = wal.replay().unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::InvalidData);
+        std::fs::remove_file(&path).unwrap();
+    }
+}